@@ -0,0 +1,80 @@
+use std::fmt;
+
+use crate::ox16_pci954::is_ox16_pci954;
+use crate::pci::{
+	self,
+	PciDeviceInfo,
+	PhysicalSlot,
+	WindowMismatch,
+};
+
+use super::is_pex8112_bridge;
+
+/// PEX 8112 bridge and the OX16PCI954 devices on its secondary bus
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Card {
+	bridge: PciDeviceInfo,
+	slot: Option<PhysicalSlot>,
+	functions: Vec<PciDeviceInfo>,
+}
+
+impl Card {
+	pub fn bridge(&self) -> &PciDeviceInfo {
+		&self.bridge
+	}
+
+	pub fn slot(&self) -> Option<&PhysicalSlot> {
+		self.slot.as_ref()
+	}
+
+	/// OX16PCI954 functions behind the bridge, sorted by endpoint
+	pub fn functions(&self) -> &[PciDeviceInfo] {
+		&self.functions
+	}
+
+	/// BARs of the OX16PCI954 functions the bridge doesn't forward
+	pub fn check_bridge_windows(&self) -> crate::AResult<Vec<WindowMismatch>> {
+		let space = pci::open_config_space_readonly(self.bridge.endpoint())?;
+		let windows = pci::decode_bridge_windows(&space)?;
+		Ok(pci::check_bridge_windows(&windows, &self.functions))
+	}
+}
+
+impl fmt::Display for Card {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.slot {
+			None => write!(f, "{}", self.bridge.endpoint()),
+			Some(ref slot) => write!(f, "{} [slot {}]", self.bridge.endpoint(), slot),
+		}
+	}
+}
+
+/// Group devices into cards; doesn't check the bridge EEPROM signature
+pub fn find_cards(devices: &[PciDeviceInfo], slots: &[PhysicalSlot]) -> crate::AResult<Vec<Card>> {
+	let mut cards = Vec::new();
+	for bridge in devices {
+		if !is_pex8112_bridge(bridge) {
+			continue;
+		}
+		let bus = match bridge.secondary_bus() {
+			None => continue,
+			Some(bus) => bus,
+		};
+		let functions = devices.iter()
+			.filter(|info| info.endpoint().bus == bus && is_ox16_pci954(info))
+			.cloned()
+			.collect();
+		let slot = pci::find_physical_slot(bridge.endpoint(), slots)?.cloned();
+		cards.push(Card {
+			bridge: bridge.clone(),
+			slot,
+			functions,
+		});
+	}
+	Ok(cards)
+}
+
+/// Find all cards in the system
+pub fn list_cards() -> crate::AResult<Vec<Card>> {
+	find_cards(&pci::list_all_devices()?, &pci::list_physical_slots()?)
+}
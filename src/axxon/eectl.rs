@@ -33,6 +33,20 @@ const SAFE_WRITE_FLAGS: u32 = 0
 pub struct EeControlRead(pub u32);
 
 impl EeControlRead {
+	// idle state of an emulated controller with a present, valid EEPROM
+	pub(super) fn emulated(data: u8, address_width: AddressWidth, chip_select: bool) -> Self {
+		let address_width = match address_width {
+			AddressWidth::One => 0x01,
+			AddressWidth::Two => 0x02,
+			AddressWidth::Three => 0x03,
+		};
+		let mut value = EEPROM_VALID | EEPROM_PRESENT | address_width << EEPROM_ADDRESS_WIDTH_SHIFT | (data as u32) << 8;
+		if chip_select {
+			value |= EEPROM_CHIP_SELECT | EEPROM_CHIP_SELECT_ACTIVE;
+		}
+		EeControlRead(value)
+	}
+
 	// keep flags/bits that are safe to write back
 	//
 	// after `ee_waitidle` byte_write_start and byte_read_start should
@@ -0,0 +1,281 @@
+// PEX 8112 configuration space emulating the EEPROM controller (EECTL) with a
+// 25xx SPI EEPROM loaded from a binary dump file
+
+use std::fs;
+use std::path::{
+	Path,
+	PathBuf,
+};
+
+use crate::pci::{
+	PciBus,
+	PciConfigSpace,
+	PciConfigSpaceReadOnly,
+	PciEndpoint,
+	SlotFunction,
+};
+
+use super::consts::*;
+use super::eectl::*;
+
+const CONFIG_SPACE_SIZE: usize = 0x100;
+
+enum Command {
+	Opcode,
+	Address { write: bool, remaining: usize, address: usize },
+	Read { address: usize },
+	Write { address: usize },
+	ReadStatus,
+	Ignore,
+}
+
+struct SpiEeprom {
+	memory: Vec<u8>,
+	address_bytes: usize,
+	write_enabled: bool,
+	// None: CS not asserted
+	command: Option<Command>,
+	pending: Vec<(usize, u8)>,
+}
+
+impl SpiEeprom {
+	// shift a byte in and out; reads send zeroes
+	fn transfer(&mut self, data: u8) -> u8 {
+		let len = self.memory.len();
+		let command = self.command.get_or_insert(Command::Opcode);
+		match *command {
+			Command::Opcode => {
+				*command = match data {
+					WREN_EE_OPCODE => { self.write_enabled = true; Command::Ignore },
+					WRDI_EE_OPCODE => { self.write_enabled = false; Command::Ignore },
+					READ_STATUS_EE_OPCODE => Command::ReadStatus,
+					READ_EE_OPCODE => Command::Address { write: false, remaining: self.address_bytes, address: 0 },
+					WRITE_EE_OPCODE => Command::Address { write: true, remaining: self.address_bytes, address: 0 },
+					_ => Command::Ignore,
+				};
+				0xff
+			},
+			Command::Address { write, remaining, address } => {
+				let address = address << 8 | data as usize;
+				*command = match (remaining, write) {
+					(1, false) => Command::Read { address },
+					(1, true) => Command::Write { address },
+					_ => Command::Address { write, remaining: remaining - 1, address },
+				};
+				0xff
+			},
+			Command::Read { ref mut address } => {
+				let data = self.memory[*address % len];
+				*address += 1;
+				data
+			},
+			Command::Write { ref mut address } => {
+				self.pending.push((*address % len, data));
+				*address += 1;
+				0xff
+			},
+			// WIP is never set, writes finish immediately
+			Command::ReadStatus => (self.write_enabled as u8) << 1,
+			Command::Ignore => 0xff,
+		}
+	}
+
+	// CS deasserted: starts the write cycle of a WRITE
+	fn deselect(&mut self) {
+		if let Some(Command::Write { .. }) = self.command {
+			if self.write_enabled {
+				for (address, data) in self.pending.drain(..) {
+					self.memory[address] = data;
+				}
+			}
+			self.write_enabled = false;
+		}
+		self.pending.clear();
+		self.command = None;
+	}
+}
+
+/// Emulated PEX 8112 with the EEPROM contents from a dump file; changes are
+/// written back by `save` (or when dropped)
+///
+/// The address width is derived from the dump size (one address byte up to
+/// 256 bytes, two up to 64 KiB).
+pub struct FileBridge {
+	eeprom: SpiEeprom,
+	address_width: AddressWidth,
+	main_index: u32,
+	read_data: u8,
+	config: Vec<u8>,
+	path: PathBuf,
+	saved: Vec<u8>,
+	chip_select: bool,
+}
+
+impl FileBridge {
+	pub fn open<P: AsRef<Path>>(path: P) -> crate::AResult<Self> {
+		let path = path.as_ref();
+		let memory = fs::read(path).map_err(|e| format_err!("Couldn't read EEPROM dump {:?}: {}", path, e))?;
+		ensure!(memory.len() >= EEPROM_SIGNATURE_OFFSET + 5, "EEPROM dump {:?} too small: {} bytes", path, memory.len());
+		let (address_width, address_bytes) = match memory.len() {
+			0..=0x100 => (AddressWidth::One, 1),
+			0x101..=0x1_0000 => (AddressWidth::Two, 2),
+			0x1_0001..=0x100_0000 => (AddressWidth::Three, 3),
+			len => bail!("EEPROM dump {:?} too large: {} bytes", path, len),
+		};
+
+		let mut config = vec![0u8; CONFIG_SPACE_SIZE];
+		// vendor and device id
+		config[..4].copy_from_slice(&[0xb5, 0x10, 0x12, 0x81]);
+		Ok(FileBridge {
+			eeprom: SpiEeprom {
+				memory: memory.clone(),
+				address_bytes,
+				write_enabled: false,
+				command: None,
+				pending: Vec::new(),
+			},
+			address_width,
+			main_index: 0,
+			read_data: 0,
+			config,
+			path: path.to_path_buf(),
+			saved: memory,
+			chip_select: false,
+		})
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	pub fn memory(&self) -> &[u8] {
+		&self.eeprom.memory
+	}
+
+	/// Write the dump file if the contents changed
+	pub fn save(&mut self) -> crate::AResult<()> {
+		if self.eeprom.memory == self.saved {
+			return Ok(());
+		}
+		fs::write(&self.path, &self.eeprom.memory).map_err(|e| format_err!("Couldn't write EEPROM dump {:?}: {}", self.path, e))?;
+		self.saved = self.eeprom.memory.clone();
+		Ok(())
+	}
+
+	fn eectl_write(&mut self, eectl: EeControlWrite) {
+		if !eectl.is_chip_select() {
+			self.eeprom.deselect();
+		} else if eectl.is_byte_write_start() {
+			self.eeprom.transfer(eectl.data());
+		} else if eectl.is_byte_read_start() {
+			self.read_data = self.eeprom.transfer(0);
+		}
+		self.chip_select = eectl.is_chip_select();
+	}
+
+	fn main_read(&self) -> u32 {
+		match self.main_index {
+			// PCI Express and PCI enabled, default speed
+			DEVICE_INITIALIZATION => 0b11_0011,
+			SERIAL_EEPROM_CONTROL => EeControlRead::emulated(self.read_data, self.address_width, self.chip_select).0,
+			_ => 0,
+		}
+	}
+}
+
+impl Drop for FileBridge {
+	fn drop(&mut self) {
+		if let Err(e) = self.save() {
+			error!("{}", e);
+		}
+	}
+}
+
+impl PciConfigSpaceReadOnly for FileBridge {
+	fn endpoint(&self) -> PciEndpoint {
+		// not a real device
+		PciEndpoint {
+			bus: PciBus { domain: 0xffff, bus: 0xff },
+			slot_function: SlotFunction(0xff),
+		}
+	}
+
+	fn len(&self) -> usize {
+		CONFIG_SPACE_SIZE
+	}
+
+	fn read_byte(&self, offset: usize) -> u8 {
+		self.config[offset]
+	}
+
+	fn read_dword(&self, offset: usize) -> u32 {
+		assert!(offset & 3 == 0);
+		if MAIN_CONTROL_REGISTER_DATA == offset {
+			return self.main_read();
+		}
+		let mut buf = [0u8; 4];
+		self.read_slice(offset, &mut buf);
+		u32::from_le_bytes(buf)
+	}
+
+	fn read_slice(&self, offset: usize, target: &mut [u8]) {
+		target.copy_from_slice(&self.config[offset..offset + target.len()]);
+	}
+
+	fn read_into_vec(&self) -> Vec<u8> {
+		self.config.clone()
+	}
+}
+
+impl PciConfigSpace for FileBridge {
+	fn write_byte(&mut self, offset: usize, data: u8) {
+		self.config[offset] = data;
+	}
+
+	fn write_dword(&mut self, offset: usize, data: u32) {
+		assert!(offset & 3 == 0);
+		match offset {
+			MAIN_CONTROL_REGISTER_INDEX => self.main_index = data,
+			MAIN_CONTROL_REGISTER_DATA => if SERIAL_EEPROM_CONTROL == self.main_index {
+				self.eectl_write(EeControlWrite(data));
+			},
+			_ => self.config[offset..offset + 4].copy_from_slice(&data.to_le_bytes()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::fs;
+
+	use super::FileBridge;
+	use crate::axxon::{
+		IMAGE,
+		extract_image,
+		open_flash,
+		open_flash_recovery,
+		write_image,
+	};
+	use crate::eeprom::Eeprom;
+
+	#[test]
+	fn flash_dump_file() {
+		let path = std::env::temp_dir().join(format!("pex8112-{}.bin", std::process::id()));
+		fs::write(&path, vec![0xff; 0x100]).unwrap();
+
+		let mut bridge = FileBridge::open(&path).unwrap();
+		assert!(open_flash(&mut bridge).is_err());
+		{
+			let mut flash = open_flash_recovery(&mut bridge).unwrap();
+			assert_eq!(flash.capacity().words, 0x80);
+			write_image(&mut flash, &IMAGE).unwrap();
+		}
+		bridge.save().unwrap();
+
+		let mut bridge = FileBridge::open(&path).unwrap();
+		let mut flash = open_flash(&mut bridge).unwrap();
+		assert_eq!(extract_image(&mut flash).unwrap(), &IMAGE[..]);
+		assert_eq!(&fs::read(&path).unwrap()[0x78..0x7d], b"axxon");
+		fs::remove_file(&path).unwrap();
+	}
+}
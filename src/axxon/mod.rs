@@ -3,16 +3,41 @@
 /* Chip documentation: https://www.broadcom.com/products/pcie-switches-bridges/pcie-bridges/pex8112#documentation */
 
 use std::io;
+use std::time::{
+	Duration,
+	Instant,
+};
 
+use crate::eeprom::{
+	Capacity,
+	Eeprom,
+};
 use crate::pci::{
+	self,
 	PciConfigSpace,
+	PciDeviceInfo,
 	PciEndpoint,
+	SlotFunction,
 };
 
+mod card;
 mod eectl;
+mod file;
 mod image;
+mod stats;
 
+pub use self::card::{
+	Card,
+	find_cards,
+	list_cards,
+};
+pub use self::file::FileBridge;
 pub use self::image::IMAGE;
+pub use self::stats::{
+	EeStats,
+	ee_stats,
+	reset_ee_stats,
+};
 
 #[allow(dead_code)]
 mod consts {
@@ -31,6 +56,12 @@ mod consts {
 	pub const READ_STATUS_EE_OPCODE: u8 = 0x05; // "RDSR"
 	pub const WREN_EE_OPCODE:        u8 = 0x06; // write enable (sets WEL)
 
+	// status register bits
+	pub const STATUS_WRITE_IN_PROGRESS: u8 = 0x01; // "WIP"
+
+	// smallest page size of 25xx EEPROMs; a write wraps around at the end of a page
+	pub const EEPROM_PAGE_SIZE: usize = 16;
+
 	// offset in EEPROM to write "axxon" to
 	pub const EEPROM_SIGNATURE_OFFSET: usize = 0x78;
 }
@@ -50,6 +81,7 @@ trait PciConfigSpaceEeExt: PciConfigSpace {
 	}
 
 	fn eectl_read(&mut self) -> EeControlRead {
+		stats::count_eectl_read();
 		let data = EeControlRead(self.main_read(SERIAL_EEPROM_CONTROL));
 		// TODO: debug log
 		// eprintln!("EECTL read : {:?}", data);
@@ -59,6 +91,7 @@ trait PciConfigSpaceEeExt: PciConfigSpace {
 	fn eectl_write(&mut self, data: EeControlWrite) {
 		// TODO: debug log
 		// eprintln!("EECTL write: {:?}", data);
+		stats::count_eectl_write();
 		self.main_write(SERIAL_EEPROM_CONTROL, data.0);
 	}
 
@@ -69,11 +102,14 @@ trait PciConfigSpaceEeExt: PciConfigSpace {
 			if !eectl.is_busy() {
 				return Ok(eectl)
 			}
+			stats::count_busy_poll();
 		}
 		bail!("EEPROM Timeout error - always busy!");
 	}
 
 	fn ee_off(&mut self) -> crate::AResult<()> {
+		// dropping CS starts the write cycle of a pending WRITE
+		let _defer = crate::signal::defer_termination()?;
 		let eectl = self.ee_waitidle()?;
 		if !eectl.is_off() {
 			self.eectl_write(EeControlWrite::off());
@@ -84,13 +120,16 @@ trait PciConfigSpaceEeExt: PciConfigSpace {
 	fn ee_sendbyte(&mut self, data: u8) -> crate::AResult<()> {
 		self.ee_waitidle()?;
 		self.eectl_write(EeControlWrite::write_data(data));
+		stats::count_byte_sent();
 		Ok(())
 	}
 
 	fn ee_readbyte(&mut self) -> crate::AResult<u8> {
 		self.ee_waitidle()?;
 		self.eectl_write(EeControlWrite::read_data());
-		Ok(self.ee_waitidle()?.data())
+		let data = self.ee_waitidle()?.data();
+		stats::count_byte_read();
+		Ok(data)
 	}
 }
 impl<S: PciConfigSpace+?Sized> PciConfigSpaceEeExt for S {}
@@ -98,6 +137,8 @@ impl<S: PciConfigSpace+?Sized> PciConfigSpaceEeExt for S {}
 pub struct Flash<S: PciConfigSpace> {
 	space: S,
 	address_width: AddressWidth,
+	// in bytes; None: only the address width is known
+	size: Option<usize>,
 }
 
 impl<S: PciConfigSpace> Flash<S> {
@@ -128,19 +169,74 @@ impl<S: PciConfigSpace> Flash<S> {
 	}
 
 	pub fn writer<'a>(&'a mut self, address: usize) -> crate::AResult<FlashWriter<'a, S>> {
+		let defer = crate::signal::defer_termination()?;
+		// stopping within a page write would commit a partial page
+		crate::signal::check_interrupted()?;
 		self.space.ee_off()?;
 		self.space.ee_sendbyte(WREN_EE_OPCODE)?;
 		self.space.ee_off()?;
 		self.space.ee_sendbyte(WRITE_EE_OPCODE)?;
 		self.send_address(address)?;
 
-		Ok(FlashWriter { flash: self })
+		Ok(FlashWriter { flash: self, _defer: defer })
 	}
 
 	pub fn write_byte(&mut self, address: usize, data: u8) -> crate::AResult<()> {
 		self.writer(address)?.write_byte(data)
 	}
 
+	// smallest and largest EEPROM with the address width (25xx010..25xx020,
+	// 25xx080..25xx512, 25xx1024..)
+	fn size_range(&self) -> (usize, usize) {
+		match self.address_width {
+			AddressWidth::One => (0x80, 0x100),
+			AddressWidth::Two => (0x400, 0x1_0000),
+			AddressWidth::Three => (0x2_0000, 0x100_0000),
+		}
+	}
+
+	/// Size set by `set_size`, otherwise the smallest EEPROM with the
+	/// detected address width (larger parts can't be detected)
+	pub fn size(&self) -> usize {
+		self.size.unwrap_or(self.size_range().0)
+	}
+
+	/// Whether the size was set by `set_size`
+	pub fn is_size_known(&self) -> bool {
+		self.size.is_some()
+	}
+
+	/// Set the EEPROM size in bytes (must match the address width)
+	pub fn set_size(&mut self, size: usize) -> crate::AResult<()> {
+		let (min, max) = self.size_range();
+		ensure!(size.is_power_of_two() && (min..=max).contains(&size),
+			"EEPROM size {} not possible with {:?} address byte(s) ({} to {} bytes)", size, self.address_width, min, max);
+		self.size = Some(size);
+		Ok(())
+	}
+
+	/// Wait for the internal write cycle to finish (WIP status bit)
+	pub fn wait_write_complete(&mut self) -> crate::AResult<()> {
+		let deadline = Instant::now() + Duration::from_millis(50);
+		while 0 != self.readstatus()? & STATUS_WRITE_IN_PROGRESS {
+			stats::count_write_cycle_poll();
+			ensure!(Instant::now() < deadline, "EEPROM Timeout error - write cycle didn't finish");
+		}
+		Ok(())
+	}
+
+	/// Write `data` page by page, waiting for each write cycle
+	pub fn write_pages(&mut self, mut address: usize, mut data: &[u8]) -> crate::AResult<()> {
+		while !data.is_empty() {
+			let len = data.len().min(EEPROM_PAGE_SIZE - address % EEPROM_PAGE_SIZE);
+			self.writer(address)?.write(&data[..len])?;
+			self.wait_write_complete()?;
+			address += len;
+			data = &data[len..];
+		}
+		Ok(())
+	}
+
 	pub fn reader<'a>(&'a mut self, address: usize) -> crate::AResult<FlashReader<'a, S>> {
 		self.space.ee_off()?;
 		self.space.ee_sendbyte(READ_EE_OPCODE)?;
@@ -166,6 +262,35 @@ impl<S: PciConfigSpace> Flash<S> {
 	}
 }
 
+impl<S: PciConfigSpace> Eeprom for Flash<S> {
+	fn capacity(&self) -> Capacity {
+		Capacity {
+			words: self.size(),
+			word_bits: 8,
+		}
+	}
+
+	fn read_contents(&mut self) -> crate::AResult<Vec<u16>> {
+		ensure!(self.is_size_known(), "EEPROM size unknown (only {} bytes known to exist)", self.size());
+		let mut buf = vec![0u8; self.size()];
+		self.reader(0)?.read(&mut buf)?;
+		Ok(buf.into_iter().map(u16::from).collect())
+	}
+
+	fn write_contents(&mut self, data: &[u16]) -> crate::AResult<()> {
+		ensure!(data.len() <= self.size(), "{} bytes don't fit into EEPROM ({})", data.len(), self.capacity());
+		ensure!(data.iter().all(|&w| w <= 0xff), "EEPROM data must be bytes");
+		let bytes: Vec<u8> = data.iter().map(|&w| w as u8).collect();
+		self.write_pages(0, &bytes)
+	}
+
+	fn erase_contents(&mut self) -> crate::AResult<()> {
+		ensure!(self.is_size_known(), "EEPROM size unknown (only {} bytes known to exist)", self.size());
+		let blank = vec![0xff; self.size()];
+		self.write_pages(0, &blank)
+	}
+}
+
 pub struct FlashReader<'a, S: PciConfigSpace + 'a> {
 	flash: &'a mut Flash<S>,
 }
@@ -197,8 +322,11 @@ impl<'a, S: PciConfigSpace> Iterator for FlashReader<'a, S> {
 	}
 }
 
+/// Write enabled until dropped; termination signals are deferred until
+/// then (`Flash::writer` fails once one is pending)
 pub struct FlashWriter<'a, S: PciConfigSpace + 'a> {
 	flash: &'a mut Flash<S>,
+	_defer: crate::signal::DeferTermination,
 }
 
 impl<'a, S: PciConfigSpace> FlashWriter<'a, S> {
@@ -208,7 +336,7 @@ impl<'a, S: PciConfigSpace> FlashWriter<'a, S> {
 
 	pub fn write(&mut self, data: &[u8]) -> crate::AResult<()> {
 		for b in data {
-			self.flash.space.ee_sendbyte(*b)?;
+			self.write_byte(*b)?;
 		}
 		Ok(())
 	}
@@ -324,6 +452,7 @@ pub fn open_flash<S: PciConfigSpace>(mut space: S) -> crate::AResult<Flash<S>> {
 	let mut flash = Flash {
 		space,
 		address_width,
+		size: None,
 	};
 
 	flash.verify_signature()?;
@@ -430,6 +559,7 @@ pub fn detect_address_width_allow_writing<S: PciConfigSpace>(mut space: &mut S)
 		let mut flash = Flash {
 			space: &mut space,
 			address_width: *aw,
+			size: None,
 		};
 		flash.write_byte(0, 0)?; // write data 0x00 at address 0
 		// try to read it
@@ -487,17 +617,56 @@ pub fn open_flash_recovery<S: PciConfigSpace>(mut space: S) -> crate::AResult<Fl
 	Ok(Flash {
 		space,
 		address_width,
+		size: None,
 	})
 }
 
-pub fn is_pex8112_bridge(endpoint: PciEndpoint) -> crate::AResult<bool> {
-	let vendor = endpoint.vendor()?;
-	let device = endpoint.device()?;
-	let class = endpoint.class()?;
-	Ok(
-		vendor.0 == 0x10b5 && device.0 == 0x8112
+pub fn is_pex8112_bridge(info: &PciDeviceInfo) -> bool {
+	let vendor = info.vendor();
+	let device = info.device();
+	let class = info.class();
+	vendor.0 == 0x10b5 && device.0 == 0x8112
 		&& class.class_code.0 == 0x06 // Bridge Device
 		&& class.subclass_code.0 == 0x04 // PCI-to-PCI Bridge
 		&& class.programming_interface.0 == 0x00 // Normal Decode
-	)
-}
\ No newline at end of file
+}
+
+/// Parse a PCI device address or a slot label of an Axxon card
+///
+/// A plain slot label refers to the PEX 8112 bridge in that slot, `LABEL/dev.fun`
+/// to a device on the secondary bus of the bridge (e.g. `3/01.0` for the second
+/// OX16PCI954 on the card in slot "3").
+pub fn resolve_device(s: &str) -> crate::AResult<PciEndpoint> {
+	if let Ok(ep) = s.parse::<PciEndpoint>() {
+		return Ok(ep);
+	}
+
+	let (label, slot_function) = match s.rfind('/') {
+		None => (s, None),
+		Some(pos) => (&s[..pos], Some(s[pos+1..].parse::<SlotFunction>()?)),
+	};
+
+	let slots = pci::list_physical_slots()?;
+	let slot = match pci::find_physical_slot_by_name(label, &slots) {
+		None => bail!("{:?} is neither a PCI device address nor a known slot label", s),
+		Some(slot) => slot,
+	};
+
+	for info in pci::list_all_devices()? {
+		if !is_pex8112_bridge(&info) {
+			continue;
+		}
+		if pci::find_physical_slot(info.endpoint(), &slots)? != Some(slot) {
+			continue;
+		}
+		return match slot_function {
+			None => Ok(info.endpoint()),
+			Some(slot_function) => {
+				let bus = info.secondary_bus().ok_or_else(|| format_err!("PCI {}: Bridge without secondary bus", info.endpoint()))?;
+				Ok(PciEndpoint { bus, slot_function })
+			},
+		};
+	}
+
+	bail!("No PEX 8112 bridge found in slot {:?}", label)
+}
@@ -0,0 +1,77 @@
+// Process-wide counters of the EECTL accesses; the EEPROM helpers are an
+// extension of any `PciConfigSpace`, so there is no instance to keep them in.
+
+use std::fmt;
+use std::sync::atomic::{
+	AtomicUsize,
+	Ordering,
+};
+
+static EECTL_READS: AtomicUsize = AtomicUsize::new(0);
+static EECTL_WRITES: AtomicUsize = AtomicUsize::new(0);
+static BUSY_POLLS: AtomicUsize = AtomicUsize::new(0);
+static BYTES_SENT: AtomicUsize = AtomicUsize::new(0);
+static BYTES_READ: AtomicUsize = AtomicUsize::new(0);
+static WRITE_CYCLE_POLLS: AtomicUsize = AtomicUsize::new(0);
+
+/// EEPROM controller activity of the PEX 8112 bridges
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct EeStats {
+	pub eectl_reads: usize,
+	pub eectl_writes: usize,
+	/// EECTL reads in `ee_waitidle` returning BUSY
+	pub busy_polls: usize,
+	pub bytes_sent: usize,
+	pub bytes_read: usize,
+	/// status reads with WIP (write in progress) set
+	pub write_cycle_polls: usize,
+}
+
+impl fmt::Display for EeStats {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} EECTL reads, {} EECTL writes, {} busy polls, {} bytes sent, {} bytes read, {} write cycle polls",
+			self.eectl_reads, self.eectl_writes, self.busy_polls, self.bytes_sent, self.bytes_read, self.write_cycle_polls)
+	}
+}
+
+/// Counters since the start of the process (or `reset_ee_stats`)
+pub fn ee_stats() -> EeStats {
+	EeStats {
+		eectl_reads: EECTL_READS.load(Ordering::Relaxed),
+		eectl_writes: EECTL_WRITES.load(Ordering::Relaxed),
+		busy_polls: BUSY_POLLS.load(Ordering::Relaxed),
+		bytes_sent: BYTES_SENT.load(Ordering::Relaxed),
+		bytes_read: BYTES_READ.load(Ordering::Relaxed),
+		write_cycle_polls: WRITE_CYCLE_POLLS.load(Ordering::Relaxed),
+	}
+}
+
+pub fn reset_ee_stats() {
+	for counter in &[&EECTL_READS, &EECTL_WRITES, &BUSY_POLLS, &BYTES_SENT, &BYTES_READ, &WRITE_CYCLE_POLLS] {
+		counter.store(0, Ordering::Relaxed);
+	}
+}
+
+pub(super) fn count_eectl_read() {
+	EECTL_READS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(super) fn count_eectl_write() {
+	EECTL_WRITES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(super) fn count_busy_poll() {
+	BUSY_POLLS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(super) fn count_byte_sent() {
+	BYTES_SENT.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(super) fn count_byte_read() {
+	BYTES_READ.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(super) fn count_write_cycle_poll() {
+	WRITE_CYCLE_POLLS.fetch_add(1, Ordering::Relaxed);
+}
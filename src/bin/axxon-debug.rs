@@ -8,13 +8,19 @@ extern crate log;
 extern crate axxon_ox16pci954_flash;
 use axxon_ox16pci954_flash::*;
 
+use std::fmt;
 use std::io::{
 	self,
 	Write,
 };
+use std::path::{
+	Path,
+	PathBuf,
+};
 use std::process::exit;
 
 use axxon_ox16pci954_flash::pci::PciResourceReadOnly;
+use axxon_ox16pci954_flash::serial::HardwareOperations;
 
 fn get_param<T>(matches: &clap::ArgMatches, name: &str) -> AResult<T>
 where
@@ -32,6 +38,158 @@ where
 	})
 }
 
+fn get_device(matches: &clap::ArgMatches, name: &str) -> AResult<pci::PciEndpoint> {
+	let param = match matches.value_of(name) {
+		Some(p) => p,
+		None => bail!("missing parameter {}", name),
+	};
+	axxon::resolve_device(param).map_err(|e| {
+		let msg = format!("invalid paramater {}: {}", name, e);
+		e.context(msg).into()
+	})
+}
+
+// PCI device or EEPROM dump file
+enum Target {
+	Device(pci::PciEndpoint),
+	File(PathBuf),
+}
+
+impl fmt::Display for Target {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Target::Device(ep) => write!(f, "PCI {}", ep),
+			Target::File(ref path) => write!(f, "File {:?}", path),
+		}
+	}
+}
+
+// "file:PATH" is an EEPROM dump, everything else a PCI device
+fn get_target(matches: &clap::ArgMatches, name: &str) -> AResult<Target> {
+	let param = matches.value_of(name);
+	if let Some(path) = param.and_then(|p| p.strip_prefix("file:")) {
+		return Ok(Target::File(path.into()));
+	}
+	match get_device(matches, name) {
+		Ok(ep) => Ok(Target::Device(ep)),
+		Err(e) => match param {
+			Some(p) if Path::new(p).is_file() => bail!("{} (use file:{} for an EEPROM dump file)", e, p),
+			_ => Err(e),
+		},
+	}
+}
+
+fn get_edge_delay(matches: &clap::ArgMatches) -> AResult<serial::EdgeDelay> {
+	let strategy = match matches.value_of("timing") {
+		None => serial::DelayStrategy::Sleep,
+		Some(_) => get_param(matches, "timing")?,
+	};
+	Ok(serial::EdgeDelay::new(strategy, &serial::DatasheetTiming::default()))
+}
+
+// open EEPROM pins of OX16PCI954 device, recording a trace and statistics if
+// requested
+fn with_eeprom_pins<F, R>(matches: &clap::ArgMatches, ep: pci::PciEndpoint, f: F) -> AResult<R>
+where
+	F: FnOnce(&mut dyn serial::Hardware) -> AResult<R>,
+{
+	let delay = get_edge_delay(matches)?;
+	with_trace(matches, ox16_pci954::open_eeprom_pins(ep, delay)?, f)
+}
+
+fn with_trace<H, F, R>(matches: &clap::ArgMatches, hardware: H, f: F) -> AResult<R>
+where
+	H: serial::Hardware,
+	F: FnOnce(&mut dyn serial::Hardware) -> AResult<R>,
+{
+	let trace_file = matches.value_of("trace_vcd");
+	let mut trace = serial::Trace::new(hardware);
+	trace.set_recording(trace_file.is_some());
+	let mut stats = serial::Statistics::new(trace);
+
+	let result = f(&mut stats);
+
+	if matches.is_present("stats") {
+		for operation in stats.operations() {
+			debug!("{}", operation);
+		}
+		info!("EEPROM statistics: {}", stats);
+	}
+	let trace = stats.into_inner();
+
+	if let Some(path) = trace_file {
+		let file = std::fs::File::create(path).map_err(|e| format_err!("Couldn't create {:?}: {}", path, e))?;
+		trace.write_vcd(std::io::BufWriter::new(file))?;
+		info!("Wrote {} EEPROM signal changes to {:?}", trace.samples().len(), path);
+	}
+	result
+}
+
+// open Microwire EEPROM of OX16PCI954 device (or simulate it from a dump file);
+// unless the EEPROM type was given, detect it (devices) or use the dump size
+fn with_eeprom<F, R>(matches: &clap::ArgMatches, target: &Target, f: F) -> AResult<R>
+where
+	F: FnOnce(&mut serial::Microwire<&mut dyn serial::Hardware>) -> AResult<R>,
+{
+	let part: Option<serial::Part> = match matches.value_of("eeprom") {
+		None => None,
+		Some(_) => Some(get_param(matches, "eeprom")?),
+	};
+
+	let organization = if matches.is_present("x8") {
+		serial::Organization::X8
+	} else {
+		serial::Organization::X16
+	};
+
+	let geometry = part.map(|part| part.geometry_with_organization(organization));
+	match *target {
+		Target::Device(ep) => with_eeprom_pins(matches, ep, |hardware| {
+			let mut ee = match geometry {
+				None => serial::Microwire::detect_with_organization(hardware, organization)?,
+				Some(geometry) => serial::Microwire::new(hardware, geometry),
+			};
+			f(&mut ee)
+		}),
+		Target::File(ref path) => {
+			let mut file = serial::FileEeprom::open(path, organization)?;
+			let geometry = geometry.unwrap_or_else(|| file.geometry());
+			let result = with_trace(matches, &mut file, |hardware| f(&mut serial::Microwire::new(hardware, geometry)))?;
+			file.save()?;
+			Ok(result)
+		},
+	}
+}
+
+// run `f` on the EEPROM of an OX16PCI954 device or dump file; None if the
+// device is bound to a driver (and unbinding wasn't allowed)
+fn with_ox16pci954_eeprom<F, R>(sub_m: &clap::ArgMatches, f: F) -> AResult<Option<R>>
+where
+	F: FnOnce(&Target, &mut serial::Microwire<&mut dyn serial::Hardware>) -> AResult<R>,
+{
+	let target = get_target(sub_m, "DEVICE")?;
+	let ep = match target {
+		Target::File(_) => return with_eeprom(sub_m, &target, |ee| f(&target, ee)).map(Some),
+		Target::Device(ep) => ep,
+	};
+
+	if !ox16_pci954::is_ox16_pci954(&pci::PciDeviceInfo::load(ep)?) {
+		eprintln!("Device {} is not an OX16PCI954 PCI device", ep);
+		exit(1);
+	}
+
+	with_resources_dev(ep, sub_m.is_present("unbind"), || {
+		with_eeprom(sub_m, &target, |ee| f(&target, ee))
+	})
+}
+
+fn slot_name(ep: pci::PciEndpoint, slots: &[pci::PhysicalSlot]) -> AResult<String> {
+	Ok(match pci::find_physical_slot(ep, slots)? {
+		None => "-".into(),
+		Some(slot) => slot.name().into(),
+	})
+}
+
 fn with_resources_dev<F, R>(ep: pci::PciEndpoint, allow_unbind: bool, f: F) -> AResult<Option<R>>
 where
 	F: FnOnce() -> AResult<R>,
@@ -76,14 +234,14 @@ where
 }
 
 fn dump_resource(sub_m: &clap::ArgMatches) -> AResult<()> {
-	let ep: pci::PciEndpoint = get_param(sub_m, "DEVICE")?;
+	let ep = get_device(sub_m, "DEVICE")?;
 	let resource: usize = get_param(sub_m, "RESOURCE")?;
 	let allow_unbind = sub_m.is_present("unbind");
 
 	if with_resources_dev(ep, allow_unbind, || {
 		let res = pci::open_resource_readonly(ep, resource)?;
 
-		io::stdout().write(&res.read_into_vec())?;
+		io::stdout().write_all(&res.read_into_vec())?;
 
 		Ok(())
 	})?.is_none() {
@@ -104,30 +262,92 @@ fn list_all() -> AResult<()> {
 }
 
 fn list_ox16_pci954() -> AResult<()> {
-	let mut all = pci::list_all_endpoints()?;
-	all.sort();
-	for ep in all {
-		if !ox16_pci954::is_ox16_pci954(ep)? {
+	let slots = pci::list_physical_slots()?;
+	for info in pci::list_all_devices()? {
+		if !ox16_pci954::is_ox16_pci954(&info) {
 			continue;
 		}
 
-		println!("{}", ep);
+		println!("{}\tslot {}", info.endpoint(), slot_name(info.endpoint(), &slots)?);
+	}
+
+	Ok(())
+}
+
+fn print_vpd(indent: &str, ep: pci::PciEndpoint) {
+	match ep.vpd() {
+		Err(e) => println!("{}VPD: not readable: {}", indent, e),
+		Ok(None) => println!("{}VPD: not available", indent),
+		Ok(Some(vpd)) => {
+			println!("{}VPD: {}", indent, vpd.identifier.as_ref().map(String::as_str).unwrap_or("(no identifier)"));
+			for field in &vpd.read_only {
+				println!("{}  {}", indent, field);
+			}
+			if vpd.checksum_valid == Some(false) {
+				println!("{}  (invalid checksum)", indent);
+			}
+		},
+	}
+}
+
+fn inventory() -> AResult<()> {
+	for card in axxon::list_cards()? {
+		let bridge = card.bridge();
+		let ep = bridge.endpoint();
+		println!("Card {}", card);
+		println!("  Subsystem: {}:{}", bridge.subsystem_vendor(), bridge.subsystem_device());
+		// extended configuration space is only readable by root
+		match pci::open_config_space_readonly(ep).ok().and_then(|s| pci::read_device_serial_number(&s)) {
+			None => println!("  Device Serial Number: not available"),
+			Some(dsn) => println!("  Device Serial Number: {}", dsn),
+		}
+		print_vpd("  ", ep);
+		for function in card.functions() {
+			println!("  OX16PCI954 {} ({}:{})", function.endpoint(), function.vendor(), function.device());
+			print_vpd("    ", function.endpoint());
+		}
 	}
 
 	Ok(())
 }
 
 fn info(sub_m: &clap::ArgMatches) -> AResult<()> {
-	let ep: pci::PciEndpoint = get_param(sub_m, "DEVICE")?;
+	let ep = get_device(sub_m, "DEVICE")?;
 	let allow_unbind = sub_m.is_present("unbind");
+	let json = sub_m.is_present("json");
 
-	if !ox16_pci954::is_ox16_pci954(ep)? {
+	if !ox16_pci954::is_ox16_pci954(&pci::PciDeviceInfo::load(ep)?) {
 		eprintln!("Device {} is not an OX16PCI954 PCI device", ep);
 		exit(1);
 	}
 
+	if json {
+		// only the local configuration, for scripts
+		if with_resources_dev(ep, allow_unbind, || {
+			println!("{}", ox16_pci954::decode_resource3(ep)?.to_json());
+			Ok(())
+		})?.is_none() {
+			exit(1);
+		}
+		return Ok(());
+	}
+
+	println!("Slot: {}", slot_name(ep, &pci::list_physical_slots()?)?);
+	for card in axxon::list_cards()? {
+		if !card.functions().iter().any(|f| f.endpoint() == ep) {
+			continue;
+		}
+		let mismatches = card.check_bridge_windows()?;
+		if mismatches.is_empty() {
+			println!("Bridge windows of card {}: ok", card);
+		}
+		for mismatch in mismatches {
+			println!("Bridge windows of card {}: PCI {}: {}", card, mismatch.endpoint, mismatch);
+		}
+	}
+
 	if with_resources_dev(ep, allow_unbind, || {
-		println!("{:?}", ox16_pci954::decode_resource3(ep)?);
+		println!("{}", ox16_pci954::decode_resource3(ep)?);
 
 		Ok(())
 	})?.is_none() {
@@ -137,23 +357,153 @@ fn info(sub_m: &clap::ArgMatches) -> AResult<()> {
 	Ok(())
 }
 
+fn interrupts(sub_m: &clap::ArgMatches) -> AResult<()> {
+	let interval: u64 = match sub_m.value_of("interval") {
+		None => 1,
+		Some(_) => get_param(sub_m, "interval")?,
+	};
+
+	let cards = axxon::list_cards()?;
+	// load configuration before sampling, so the sysfs reads don't distort the interval
+	let mut configs = Vec::new();
+	for card in &cards {
+		let mut card_configs = Vec::new();
+		for info in card.functions() {
+			card_configs.push(pci::InterruptConfig::load(info.endpoint())?);
+		}
+		configs.push(card_configs);
+	}
+
+	let sample = pci::sample_interrupts(std::time::Duration::from_secs(interval))?;
+
+	for (card, card_configs) in cards.iter().zip(&configs) {
+		println!("Card {}", card);
+		let mut card_irqs = std::collections::BTreeSet::new();
+
+		for (info, config) in card.functions().iter().zip(card_configs) {
+			let ep = info.endpoint();
+			let irqs = config.active_irqs();
+			let driver = match info.driver() {
+				None => "no driver".to_string(),
+				Some(driver) => format!("driver {}", driver),
+			};
+			if irqs.is_empty() {
+				println!("  {} ({}): no interrupt assigned", ep, driver);
+				continue;
+			}
+			for irq in irqs {
+				card_irqs.insert(irq);
+				let kind = if config.uses_msi() { "MSI" } else { "IRQ" };
+				let shared: Vec<String> = card.functions().iter().zip(card_configs)
+					.filter(|(other, other_config)| other.endpoint() != ep && other_config.active_irqs().contains(&irq))
+					.map(|(other, _)| other.endpoint().to_string())
+					.collect();
+				print!("  {} ({}): {} {}: {} total, {:.1}/s",
+					ep, driver, kind, irq, sample.after.total(irq), sample.rate(irq));
+				if !shared.is_empty() {
+					print!(" [shared with {}]", shared.join(", "));
+				}
+				println!();
+				if ep.slot_function.function() != 0 && info.driver().is_some() && !shared.is_empty() {
+					warn!("PCI {}: function {} is driven by {} and shares {} {} with other functions", ep, ep.slot_function.function(), driver, kind, irq);
+				}
+			}
+		}
+
+		let card_rate: f64 = card_irqs.iter().map(|irq| sample.rate(*irq)).sum();
+		println!("  card total: {:.1}/s", card_rate);
+	}
+
+	Ok(())
+}
+
+// annotated OX16PCI954 program; words after it are only counted
+fn print_disassembly(words: &[u16]) -> AResult<()> {
+	let lines = ox16_pci954::disassemble(words)?;
+	for line in &lines {
+		println!("{}", line);
+	}
+	let used = words[lines.len()..].iter().filter(|&&word| word != 0xffff).count();
+	if used > 0 {
+		info!("{} non-blank words after the program", used);
+	}
+	Ok(())
+}
+
 fn dump_eeprom(sub_m: &clap::ArgMatches) -> AResult<()> {
-	let ep: pci::PciEndpoint = get_param(sub_m, "DEVICE")?;
-	let allow_unbind = sub_m.is_present("unbind");
+	let reads: usize = match sub_m.value_of("reads") {
+		None => 1,
+		Some(_) => get_param(sub_m, "reads")?,
+	};
+	ensure!(reads % 2 == 1, "Number of reads must be odd");
+	let disassemble = sub_m.is_present("disassemble");
+
+	fn dump<H: serial::HardwareOperations>(target: &Target, ee: &mut H, reads: usize, disassemble: bool) -> AResult<()> {
+		let geometry = ee.geometry();
+		info!("{}: EEPROM {}", target, geometry);
+		let digits = geometry.word_bits() / 4;
+		let words: Vec<u16> = if 1 == reads {
+			ee.read_all()?.collect()
+		} else {
+			ee.read_all_consensus(reads)?
+		};
+		if disassemble {
+			ensure!(geometry.organization() == serial::Organization::X16, "Can only disassemble x16 EEPROMs");
+			match print_disassembly(&words) {
+				Ok(()) => return Ok(()),
+				// still show the raw words
+				Err(e) => warn!("{}: {}", target, e),
+			}
+		}
+		for (address, word) in words.into_iter().enumerate() {
+			println!("@{:02x}: {:0digits$x}", address, word, digits = digits);
+		}
+		Ok(())
+	}
 
-	if !ox16_pci954::is_ox16_pci954(ep)? {
-		eprintln!("Device {} is not an OX16PCI954 PCI device", ep);
+	if with_ox16pci954_eeprom(sub_m, |target, ee| dump(target, ee, reads, disassemble))?.is_none() {
 		exit(1);
 	}
 
-	if with_resources_dev(ep, allow_unbind, || {
-		use axxon_ox16pci954_flash::serial::HardwareOperations;
+	Ok(())
+}
 
-		let mut ee = ox16_pci954::open_eeprom(ep)?;
-		for (address, word) in ee.read_all()?.enumerate() {
-			println!("@{:02x}: {:04x}", address, word);
+fn blank_check(sub_m: &clap::ArgMatches) -> AResult<()> {
+	let blank = match with_ox16pci954_eeprom(sub_m, |_, ee| {
+		let geometry = ee.geometry();
+		let used = ee.blank_check()?;
+		let digits = geometry.word_bits() / 4;
+		for &(address, word) in &used {
+			println!("@{:02x}: {:0digits$x}", address, word, digits = digits);
+		}
+		if used.is_empty() {
+			println!("EEPROM {} is blank", geometry);
+		} else if used.len() == geometry.words() && used.iter().all(|&(_, word)| 0 == word) {
+			println!("All words read as zero: EEPROM missing or DO stuck low?");
+		} else {
+			println!("{} of {} words not blank", used.len(), geometry.words());
 		}
+		Ok(used.is_empty())
+	})? {
+		None => exit(1),
+		Some(blank) => blank,
+	};
+	if !blank {
+		exit(2);
+	}
+
+	Ok(())
+}
 
+fn fill(sub_m: &clap::ArgMatches) -> AResult<()> {
+	let word_s = sub_m.value_of("WORD").unwrap();
+	let word = u16::from_str_radix(word_s.trim_start_matches("0x"), 16)
+		.map_err(|e| format_err!("invalid word {:?}: {}", word_s, e))?;
+
+	if with_ox16pci954_eeprom(sub_m, |_, ee| {
+		ensure!(u32::from(word) >> ee.geometry().word_bits() == 0, "word {:04x} too large for EEPROM {}", word, ee.geometry());
+		ee.fill(word)?;
+		println!("Filled EEPROM {} with {:04x}", ee.geometry(), word);
 		Ok(())
 	})?.is_none() {
 		exit(1);
@@ -162,20 +512,162 @@ fn dump_eeprom(sub_m: &clap::ArgMatches) -> AResult<()> {
 	Ok(())
 }
 
-fn axxon_verify_eeprom(sub_m: &clap::ArgMatches) -> AResult<()> {
-	let ep: pci::PciEndpoint = get_param(sub_m, "DEVICE")?;
+// compile an OX16PCI954 program in text format
+fn load_program(path: &str) -> AResult<ox16_pci954::Program> {
+	let text = std::fs::read_to_string(path).map_err(|e| format_err!("Couldn't read {:?}: {}", path, e))?;
+	ox16_pci954::compile(&text).map_err(|e| format_err!("{:?}: {}", path, e))
+}
 
-	if ep.vendor()?.0 != 0x10B5 || ep.device()?.0 != 0x8112 {
-		eprintln!("Device {} is not an Axxon PCI device", ep);
+// log lints; returns whether there were errors
+fn report_lints(lints: &[ox16_pci954::Lint]) -> bool {
+	for lint in lints {
+		match lint.severity {
+			ox16_pci954::Severity::Warning => warn!("{}", lint),
+			ox16_pci954::Severity::Error => error!("{}", lint),
+		}
+	}
+	lints.iter().any(|lint| lint.severity == ox16_pci954::Severity::Error)
+}
+
+fn flash_program(sub_m: &clap::ArgMatches) -> AResult<()> {
+	let words = load_program(sub_m.value_of("FILE").unwrap())?.serialize()?;
+	let force = sub_m.is_present("force");
+
+	if with_ox16pci954_eeprom(sub_m, |target, ee| {
+		let lints = ox16_pci954::lint(&words, Some(ee.geometry().words()));
+		if report_lints(&lints) && !force {
+			bail!("{}: Not flashing program with lint errors (use --force to override)", target);
+		}
+		let changed = ox16_pci954::update_program(ee, &words)?;
+		println!("{}: Updated {} of {} program words", target, changed, words.len());
+		Ok(())
+	})?.is_none() {
 		exit(1);
 	}
 
-	let image = with_configspace_dev(ep, || {
-		let s = pci::open_config_space_readwrite(ep)?;
-		let mut flash = axxon::open_flash(s)?;
+	Ok(())
+}
 
-		axxon::extract_image(&mut flash)
-	})?;
+fn lint(sub_m: &clap::ArgMatches) -> AResult<()> {
+	let words = load_program(sub_m.value_of("FILE").unwrap())?.serialize()?;
+	let capacity: Option<serial::Part> = match sub_m.value_of("eeprom") {
+		None => None,
+		Some(_) => Some(get_param(sub_m, "eeprom")?),
+	};
+	let lints = ox16_pci954::lint(&words, capacity.map(|part| part.geometry().words()));
+	if report_lints(&lints) {
+		exit(2);
+	}
+	println!("{} words, {} warnings", words.len(), lints.len());
+	Ok(())
+}
+
+fn compile(sub_m: &clap::ArgMatches) -> AResult<()> {
+	let words = load_program(sub_m.value_of("FILE").unwrap())?.serialize()?;
+	let output = sub_m.value_of("OUTPUT").unwrap();
+	serial::write_dump(output, &words, serial::Organization::X16)?;
+	info!("Wrote {} words to {:?}", words.len(), output);
+	Ok(())
+}
+
+fn decompile(sub_m: &clap::ArgMatches) -> AResult<()> {
+	let path = sub_m.value_of("FILE").unwrap();
+	let words = serial::read_dump(path, serial::Organization::X16)?;
+	let program = ox16_pci954::Program::parse(&words)?;
+	print!("{}", ox16_pci954::decompile(&program));
+	Ok(())
+}
+
+fn measure_clock(sub_m: &clap::ArgMatches) -> AResult<()> {
+	let ep = get_device(sub_m, "DEVICE")?;
+	let allow_unbind = sub_m.is_present("unbind");
+	let cycles: usize = match sub_m.value_of("cycles") {
+		None => 10000,
+		Some(_) => get_param(sub_m, "cycles")?,
+	};
+	ensure!(cycles > 0, "need at least one clock cycle");
+
+	if !ox16_pci954::is_ox16_pci954(&pci::PciDeviceInfo::load(ep)?) {
+		eprintln!("Device {} is not an OX16PCI954 PCI device", ep);
+		exit(1);
+	}
+
+	let delay = get_edge_delay(sub_m)?;
+
+	if with_resources_dev(ep, allow_unbind, || {
+		with_eeprom_pins(sub_m, ep, |hardware| {
+			let rate = serial::measure_clock_rate(hardware, cycles);
+			println!("timing {} (minimum edge {:?}): {}", delay.strategy, delay.edge, rate);
+			Ok(())
+		})
+	})?.is_none() {
+		exit(1);
+	}
+
+	Ok(())
+}
+
+fn disassemble(sub_m: &clap::ArgMatches) -> AResult<()> {
+	let path = sub_m.value_of("FILE").unwrap();
+	let words = serial::read_dump(path, serial::Organization::X16)?;
+	print_disassembly(&words)
+}
+
+fn decode_trace(sub_m: &clap::ArgMatches) -> AResult<()> {
+	let path = sub_m.value_of("FILE").unwrap();
+	let text = std::fs::read_to_string(path).map_err(|e| format_err!("Couldn't read {:?}: {}", path, e))?;
+	let samples = if path.ends_with(".csv") {
+		serial::parse_csv(&text)?
+	} else {
+		serial::parse_vcd(&text)?
+	};
+
+	let organization = if sub_m.is_present("x8") {
+		serial::Organization::X8
+	} else {
+		serial::Organization::X16
+	};
+	let part: serial::Part = match sub_m.value_of("eeprom") {
+		None => serial::Part::C46,
+		Some(_) => get_param(sub_m, "eeprom")?,
+	};
+	let geometry = part.geometry_with_organization(organization);
+
+	let mut errors = 0;
+	for frame in serial::decode(&samples, geometry) {
+		if !frame.errors.is_empty() {
+			errors += 1;
+		}
+		println!("{}", frame);
+	}
+	if errors > 0 {
+		eprintln!("{} frames with protocol errors", errors);
+		exit(1);
+	}
+
+	Ok(())
+}
+
+fn axxon_verify_eeprom(sub_m: &clap::ArgMatches) -> AResult<()> {
+	let image = match get_target(sub_m, "DEVICE")? {
+		Target::File(path) => {
+			let mut flash = axxon::open_flash(axxon::FileBridge::open(path)?)?;
+			axxon::extract_image(&mut flash)?
+		},
+		Target::Device(ep) => {
+			if ep.vendor()?.0 != 0x10B5 || ep.device()?.0 != 0x8112 {
+				eprintln!("Device {} is not an Axxon PCI device", ep);
+				exit(1);
+			}
+
+			with_configspace_dev(ep, || {
+				let s = pci::open_config_space_readwrite(ep)?;
+				let mut flash = axxon::open_flash(s)?;
+
+				axxon::extract_image(&mut flash)
+			})?
+		},
+	};
 
 	if &image[..] == &axxon::IMAGE[..] {
 		println!("Image verified successfully");
@@ -202,7 +694,15 @@ fn axxon_verify_eeprom(sub_m: &clap::ArgMatches) -> AResult<()> {
 }
 
 fn axxon_dump_eeprom(sub_m: &clap::ArgMatches) -> AResult<()> {
-	let ep: pci::PciEndpoint = get_param(sub_m, "DEVICE")?;
+	let ep = match get_target(sub_m, "DEVICE")? {
+		Target::File(path) => {
+			let mut flash = axxon::open_flash_recovery(axxon::FileBridge::open(path)?)?;
+			let image = axxon::extract_image(&mut flash)?;
+			io::stdout().write_all(&image)?;
+			return Ok(());
+		},
+		Target::Device(ep) => ep,
+	};
 
 	if ep.vendor()?.0 != 0x10B5 || ep.device()?.0 != 0x8112 {
 		eprintln!("Device {} is not an Axxon PCI device", ep);
@@ -214,7 +714,7 @@ fn axxon_dump_eeprom(sub_m: &clap::ArgMatches) -> AResult<()> {
 		let mut flash = axxon::open_flash_recovery(s)?;
 
 		let image = axxon::extract_image(&mut flash)?;
-		io::stdout().write(&image)?;
+		io::stdout().write_all(&image)?;
 
 		Ok(())
 	})?;
@@ -226,18 +726,90 @@ fn main_app() -> AResult<()> {
 	let matches = clap_app!(@app (app_from_crate!())
 		(@setting SubcommandRequiredElseHelp)
 		(global_setting: clap::AppSettings::VersionlessSubcommands)
+		(@arg trace_vcd: --("trace-vcd") +takes_value +global "record OX16PCI954 EEPROM signals to VCD file (e.g. for GTKWave)")
+		(@arg stats: --stats +global "log EEPROM operation statistics (pin accesses, clock cycles, busy polls)")
 		(@subcommand list =>
 			(about: "list OX16PCI954 PCI devices")
 		)
+		(@subcommand inventory =>
+			(about: "list Axxon cards with identification data (serial number, VPD)")
+		)
 		(@subcommand info =>
 			(about: "show info for OX16PCI954 PCI device")
 			(@arg unbind: -u --unbind "temporarily unbind driver if present")
-			(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun)")
+			(@arg json: --json "only print the local configuration registers as JSON")
+			(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun or SLOT-LABEL[/dev.fun])")
+		)
+		(@subcommand interrupts =>
+			(about: "show interrupt configuration and activity of Axxon cards")
+			(@arg interval: -i --interval +takes_value "sampling interval in seconds (default: 1)")
 		)
 		(@subcommand dump_eeprom =>
 			(about: "dump EEPROM for OX16PCI954 PCI device")
 			(@arg unbind: -u --unbind "temporarily unbind driver if present")
-			(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun)")
+			(@arg eeprom: -e --eeprom +takes_value "EEPROM type (93C46, 93C56, 93C66, 93C76, 93C86; default: detect, or the size of a dump file)")
+			(@arg x8: --x8 "EEPROM uses x8 organization (ORG pin low)")
+			(@arg timing: -t --timing +takes_value "bit-bang timing (sleep, spin, readback; default: sleep)")
+			(@arg reads: -r --reads +takes_value "read N times and use the majority of each bit (odd; default: 1)")
+			(@arg disassemble: -d --disassemble "print the OX16PCI954 program with the meaning of each word")
+			(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun or SLOT-LABEL[/dev.fun]) or file:PATH for an EEPROM dump")
+		)
+		(@subcommand blank_check =>
+			(about: "check whether EEPROM for OX16PCI954 PCI device is erased (exit code 2 if not)")
+			(@arg unbind: -u --unbind "temporarily unbind driver if present")
+			(@arg eeprom: -e --eeprom +takes_value "EEPROM type (93C46, 93C56, 93C66, 93C76, 93C86; default: detect, or the size of a dump file)")
+			(@arg x8: --x8 "EEPROM uses x8 organization (ORG pin low)")
+			(@arg timing: -t --timing +takes_value "bit-bang timing (sleep, spin, readback; default: sleep)")
+			(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun or SLOT-LABEL[/dev.fun]) or file:PATH for an EEPROM dump")
+		)
+		(@subcommand fill =>
+			(about: "fill EEPROM for OX16PCI954 PCI device with a word and verify it (destroys the configuration!)")
+			(@arg unbind: -u --unbind "temporarily unbind driver if present")
+			(@arg eeprom: -e --eeprom +takes_value "EEPROM type (93C46, 93C56, 93C66, 93C76, 93C86; default: detect, or the size of a dump file)")
+			(@arg x8: --x8 "EEPROM uses x8 organization (ORG pin low)")
+			(@arg timing: -t --timing +takes_value "bit-bang timing (sleep, spin, readback; default: sleep)")
+			(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun or SLOT-LABEL[/dev.fun]) or file:PATH for an EEPROM dump")
+			(@arg WORD: +required "word (hex) to write into all addresses")
+		)
+		(@subcommand flash_program =>
+			(about: "write an OX16PCI954 program in text format (see decompile) to the EEPROM, keeping unchanged words")
+			(@arg unbind: -u --unbind "temporarily unbind driver if present")
+			(@arg eeprom: -e --eeprom +takes_value "EEPROM type (93C46, 93C56, 93C66, 93C76, 93C86; default: detect, or the size of a dump file)")
+			(@arg timing: -t --timing +takes_value "bit-bang timing (sleep, spin, readback; default: sleep)")
+			(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun or SLOT-LABEL[/dev.fun]) or file:PATH for an EEPROM dump")
+			(@arg force: -f --force "flash even if the program has lint errors")
+			(@arg FILE: +required "program in text format")
+		)
+		(@subcommand lint =>
+			(about: "check an OX16PCI954 program before flashing it (exit code 2 on errors)")
+			(@arg eeprom: -e --eeprom +takes_value "EEPROM type to check the program size against (93C46, 93C56, 93C66, 93C76, 93C86)")
+			(@arg FILE: +required "program in text format")
+		)
+		(@subcommand compile =>
+			(about: "compile an OX16PCI954 program in text format to an EEPROM image")
+			(@arg FILE: +required "program in text format")
+			(@arg OUTPUT: +required "EEPROM image file (*.txt: dump_eeprom format, otherwise binary)")
+		)
+		(@subcommand decompile =>
+			(about: "print an OX16PCI954 EEPROM image as program in text format")
+			(@arg FILE: +required "EEPROM image or dump file (*.txt: dump_eeprom output, otherwise binary)")
+		)
+		(@subcommand measure_clock =>
+			(about: "measure achieved EEPROM clock rate for OX16PCI954 PCI device")
+			(@arg unbind: -u --unbind "temporarily unbind driver if present")
+			(@arg timing: -t --timing +takes_value "bit-bang timing (sleep, spin, readback; default: sleep)")
+			(@arg cycles: -n --cycles +takes_value "number of clock cycles (default: 10000)")
+			(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun or SLOT-LABEL[/dev.fun])")
+		)
+		(@subcommand disassemble =>
+			(about: "print the meaning of each word of an OX16PCI954 EEPROM image")
+			(@arg FILE: +required "EEPROM image or dump file (*.txt: dump_eeprom output, otherwise binary)")
+		)
+		(@subcommand decode_trace =>
+			(about: "decode Microwire instructions from a recorded EEPROM trace (VCD or logic analyzer CSV)")
+			(@arg eeprom: -e --eeprom +takes_value "EEPROM type (93C46, 93C56, 93C66, 93C76, 93C86; default: 93C46)")
+			(@arg x8: --x8 "EEPROM uses x8 organization (ORG pin low)")
+			(@arg FILE: +required "trace file (*.csv: columns Time [s], CS, CLK, DI, DO; otherwise VCD)")
 		)
 		(@subcommand list_all =>
 			(about: "list all PCI devices")
@@ -245,7 +817,7 @@ fn main_app() -> AResult<()> {
 		(@subcommand dump_resource =>
 			(about: "dumps PCI resource region")
 			(@arg unbind: -u --unbind "temporarily unbind driver if present")
-			(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun)")
+			(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun or SLOT-LABEL[/dev.fun])")
 			(@arg RESOURCE: +required "Resource number to dump")
 		)
 		(@subcommand axxon =>
@@ -253,11 +825,11 @@ fn main_app() -> AResult<()> {
 			(@setting SubcommandRequiredElseHelp)
 			(@subcommand verify =>
 				(about: "verify flash image")
-				(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun)")
+				(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun or SLOT-LABEL[/dev.fun]) or file:PATH for an EEPROM dump")
 			)
 			(@subcommand dump_eeprom =>
 				(about: "dump EEPROM for AXXON PCI device as binary to stdout")
-				(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun)")
+				(@arg DEVICE: +required "PCI device to use ([bus:]slot:dev.fun or SLOT-LABEL[/dev.fun]) or file:PATH for an EEPROM dump")
 			)
 		)
 	).get_matches();
@@ -266,27 +838,66 @@ fn main_app() -> AResult<()> {
 		("list", _) => {
 			list_ox16_pci954()
 		}
+		("inventory", _) => {
+			inventory()
+		}
 		("info", Some(sub_m)) => {
 			info(sub_m)
 		}
+		("interrupts", Some(sub_m)) => {
+			interrupts(sub_m)
+		}
 		("dump_eeprom", Some(sub_m)) => {
 			dump_eeprom(sub_m)
 		}
+		("blank_check", Some(sub_m)) => {
+			blank_check(sub_m)
+		},
+		("fill", Some(sub_m)) => {
+			fill(sub_m)
+		},
+		("flash_program", Some(sub_m)) => {
+			flash_program(sub_m)
+		},
+		("lint", Some(sub_m)) => {
+			lint(sub_m)
+		},
+		("compile", Some(sub_m)) => {
+			compile(sub_m)
+		},
+		("decompile", Some(sub_m)) => {
+			decompile(sub_m)
+		},
+		("measure_clock", Some(sub_m)) => {
+			measure_clock(sub_m)
+		},
+		("disassemble", Some(sub_m)) => {
+			disassemble(sub_m)
+		},
+		("decode_trace", Some(sub_m)) => {
+			decode_trace(sub_m)
+		},
 		("list_all", _) => {
 			list_all()
 		}
 		("dump_resource", Some(sub_m)) => {
 			dump_resource(sub_m)
 		},
-		("axxon", Some(sub_m)) => match sub_m.subcommand() {
-			("verify", Some(sub_sub_m)) => {
-				axxon_verify_eeprom(sub_sub_m)
-			},
-			("dump_eeprom", Some(sub_m)) => {
-				axxon_dump_eeprom(sub_m)
+		("axxon", Some(sub_m)) => {
+			let result = match sub_m.subcommand() {
+				("verify", Some(sub_sub_m)) => {
+					axxon_verify_eeprom(sub_sub_m)
+				},
+				("dump_eeprom", Some(sub_m)) => {
+					axxon_dump_eeprom(sub_m)
+				}
+				("", _) => bail!("no subcommand"),
+				(cmd, _) => bail!("not implemented subcommand for 'axxon' {:?}", cmd),
+			};
+			if matches.is_present("stats") {
+				info!("PEX 8112 EEPROM statistics: {}", axxon::ee_stats());
 			}
-			("", _) => bail!("no subcommand"),
-			(cmd, _) => bail!("not implemented subcommand for 'axxon' {:?}", cmd),
+			result
 		},
 		("", _) => bail!("no subcommand"),
 		(cmd, _) => bail!("not implemented subcommand {:?}", cmd),
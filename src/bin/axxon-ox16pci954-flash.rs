@@ -10,26 +10,160 @@ use axxon_ox16pci954_flash::*;
 
 use std::process::exit;
 
+fn get_param<T>(matches: &clap::ArgMatches, name: &str) -> AResult<T>
+where
+	T: std::str::FromStr,
+	failure::Error: From<<T as std::str::FromStr>::Err>,
+{
+	let param = match matches.value_of(name) {
+		Some(p) => p,
+		None => bail!("missing parameter {}", name),
+	};
+	param.parse::<T>().map_err(|e| {
+		let e = failure::Error::from(e);
+		let msg = format!("invalid paramater {}: {}", name, e);
+		e.context(msg).into()
+	})
+}
+
+// device address and physical slot label (if known) for log output
+fn location(ep: pci::PciEndpoint, slots: &[pci::PhysicalSlot]) -> AResult<String> {
+	Ok(match pci::find_physical_slot(ep, slots)? {
+		None => ep.to_string(),
+		Some(slot) => format!("{} [slot {}]", ep, slot),
+	})
+}
+
+// check (and flash) the PEX 8112 image; returns whether flashing is needed
+fn check_bridge<S: pci::PciConfigSpace>(loc: &str, flash: &mut axxon::Flash<S>, bridge_image: &[u8], flash_devices: bool) -> AResult<bool> {
+	if bridge_image != &axxon::IMAGE[..] {
+		info!("{}: Axxon PCI bridge image not up to date", loc);
+		if !flash_devices {
+			return Ok(true);
+		}
+		if let Err(e) = axxon::write_image(flash, &axxon::IMAGE) {
+			error!("{}: Failed to flash Axxon PCI bridge image: {}", loc, e);
+			bail!("Failed to flash");
+		}
+	} else {
+		info!("{}: Axxon PCI bridge image up to date", loc);
+	}
+	Ok(false)
+}
+
+// check (and flash) the OX16PCI954 image; returns whether flashing is needed
+fn check_ox16pci954<H: serial::HardwareOperations>(loc: &str, ee: &mut H, flash_devices: bool, reads: usize) -> AResult<bool> {
+	let image = if 1 == reads {
+		ox16_pci954::read_flash_program(ee)?
+	} else {
+		ox16_pci954::read_flash_program_consensus(ee, reads)?
+	};
+	if image != &ox16_pci954::IMAGE[..] {
+		info!("{}: OX16PCI954 image not up to date", loc);
+		if !flash_devices {
+			return Ok(true);
+		}
+		match ox16_pci954::update_program(ee, &ox16_pci954::IMAGE) {
+			Err(e) => {
+				error!("{}: Failed to flash OX16PCI954 image: {}", loc, e);
+				bail!("Failed to flash");
+			},
+			Ok(changed) => info!("{}: Updated {} words of OX16PCI954 image", loc, changed),
+		}
+	} else {
+		info!("{}: OX16PCI954 image up to date", loc);
+	}
+	Ok(false)
+}
+
+fn log_stats<H: serial::Hardware>(loc: &str, stats: &serial::Statistics<H>) {
+	for operation in stats.operations() {
+		debug!("{}: {}", loc, operation);
+	}
+	info!("{}: OX16PCI954 EEPROM statistics: {}", loc, stats);
+}
+
+// check (and flash) EEPROM dump files instead of devices
+fn check_dump_files(matches: &clap::ArgMatches, flash_devices: bool, reads: usize) -> AResult<bool> {
+	let show_stats = matches.is_present("stats");
+	let mut need_flashing = false;
+	for path in matches.values_of("bridge_dump").into_iter().flatten() {
+		let loc = format!("File {:?}", path);
+		let mut bridge = axxon::FileBridge::open(path)?;
+		axxon::reset_ee_stats();
+		let mut flash = axxon::open_flash_recovery(&mut bridge)?;
+		let image = axxon::extract_image(&mut flash).unwrap_or_else(|e| {
+			warn!("{}: no valid image: {}", loc, e);
+			Vec::new()
+		});
+		need_flashing |= check_bridge(&loc, &mut flash, &image, flash_devices)?;
+		if show_stats {
+			info!("{}: PEX 8112 EEPROM statistics: {}", loc, axxon::ee_stats());
+		}
+		bridge.save()?;
+	}
+	for path in matches.values_of("ox16pci954_dump").into_iter().flatten() {
+		let loc = format!("File {:?}", path);
+		let mut file = serial::FileEeprom::open(path, serial::Organization::X16)?;
+		// the dump size gives the exact part
+		let geometry = file.geometry();
+		let mut ee = serial::Microwire::new(serial::Statistics::new(&mut file), geometry);
+		need_flashing |= check_ox16pci954(&loc, &mut ee, flash_devices, reads)?;
+		if show_stats {
+			log_stats(&loc, &ee.into_inner());
+		}
+		file.save()?;
+	}
+	Ok(need_flashing)
+}
+
 fn main_app() -> AResult<()> {
 	let matches = clap_app!(@app (app_from_crate!())
 		(global_setting: clap::AppSettings::VersionlessSubcommands)
 		(@arg flash: --flash "Flash devices (if not using target images already)")
+		(@arg timing: -t --timing +takes_value "EEPROM bit-bang timing (sleep, spin, readback; default: sleep)")
+		(@arg reads: -r --reads +takes_value "Read OX16PCI954 EEPROMs N times and use the majority of each bit (odd; default: 1)")
+		(@arg bridge_dump: --("bridge-dump") +takes_value +multiple number_of_values(1) "Check PEX 8112 EEPROM dump file (binary) instead of devices")
+		(@arg stats: --stats "Log EEPROM operation statistics (pin accesses, clock cycles, busy polls)")
+		(@arg ox16pci954_dump: --("ox16pci954-dump") +takes_value +multiple number_of_values(1) "Check OX16PCI954 EEPROM dump file (*.txt: dump_eeprom output, otherwise binary) instead of devices")
 	).get_matches();
 	let flash_devices = matches.is_present("flash");
+	let strategy = match matches.value_of("timing") {
+		None => serial::DelayStrategy::Sleep,
+		Some(_) => get_param(&matches, "timing")?,
+	};
+	let delay = serial::EdgeDelay::new(strategy, &serial::DatasheetTiming::default());
+	let reads: usize = match matches.value_of("reads") {
+		None => 1,
+		Some(_) => get_param(&matches, "reads")?,
+	};
+	ensure!(reads % 2 == 1, "Number of reads must be odd");
+	let show_stats = matches.is_present("stats");
 	let mut need_flashing = false;
 
+	if matches.is_present("bridge_dump") || matches.is_present("ox16pci954_dump") {
+		if check_dump_files(&matches, flash_devices, reads)? {
+			info!("One or multiple dump files are not using the target images");
+			exit(11);
+		}
+		return Ok(());
+	}
+
+	let slots = pci::list_physical_slots()?;
 	let mut ox16pci954_busses = std::collections::HashSet::new();
 	// list of endpoints (function 1) that should be checked because function 0 was in use
 	let mut ox16pci954_check_f1 = std::collections::HashSet::new();
-	let mut all = pci::list_all_endpoints()?;
-	all.sort();
-	for ep in all {
-		if axxon::is_pex8112_bridge(ep)? {
+	let devices = pci::list_all_devices()?;
+	for info in &devices {
+		let ep = info.endpoint();
+		if axxon::is_pex8112_bridge(info) {
+			let loc = location(ep, &slots)?;
 			let _se = ep.scoped_enable()?;
 			let s = pci::open_config_space_readwrite(ep)?;
+			axxon::reset_ee_stats();
 			let mut flash = match axxon::open_flash(s) {
 				Err(e) => {
-					error!("PCI {}: probably not an AXXON device: {:?}", ep, e);
+					error!("PCI {}: probably not an AXXON device: {:?}", loc, e);
 					continue;
 				},
 				Ok(f) => f,
@@ -37,72 +171,60 @@ fn main_app() -> AResult<()> {
 
 			let bridge_image = match axxon::extract_image(&mut flash) {
 				Err(e) => {
-					error!("PCI {}: failed to read image: {:?}", ep, e);
+					error!("PCI {}: failed to read image: {:?}", loc, e);
 					break;
 				}
 				Ok(i) => i,
 			};
-
-			if bridge_image != &axxon::IMAGE[..] {
-				info!("PCI {}: Axxon PCI bridge image not up to date", ep);
-				if flash_devices {
-					if let Err(e) = axxon::write_image(&mut flash, &axxon::IMAGE) {
-						error!("PCI {}: Failed to flash Axxon PCI bridge image: {}", ep, e);
-						bail!("Failed to flash");
-					}
-				} else {
-					need_flashing = true;
-				}
-			} else {
-				info!("PCI {}: Axxon PCI bridge image up to date", ep);
+			need_flashing |= check_bridge(&format!("PCI {}", loc), &mut flash, &bridge_image, flash_devices)?;
+			if show_stats {
+				info!("PCI {}: PEX 8112 EEPROM statistics: {}", loc, axxon::ee_stats());
 			}
 
-			let bus = ep.secondary_bus()?;
+			let bus = info.secondary_bus().ok_or_else(|| format_err!("PCI {}: Bridge without secondary bus", loc))?;
 			if bus < ep.bus {
-				error!("PCI {}: Bridge has a secondary bus ({}) with an id less than its own, won't find OX16PCI954 devices", ep, bus);
+				error!("PCI {}: Bridge has a secondary bus ({}) with an id less than its own, won't find OX16PCI954 devices", loc, bus);
 			}
 			ox16pci954_busses.insert(bus);
-		} else if ox16_pci954::is_ox16_pci954(ep)? {
+
+			let downstream: Vec<_> = devices.iter().filter(|d| d.endpoint().bus == bus).cloned().collect();
+			let windows = pci::decode_bridge_windows(&pci::open_config_space_readonly(ep)?)?;
+			for mismatch in pci::check_bridge_windows(&windows, &downstream) {
+				error!("PCI {}: {} (behind bridge {})", mismatch.endpoint, mismatch, loc);
+			}
+		} else if ox16_pci954::is_ox16_pci954(info) {
+			let loc = location(ep, &slots)?;
 			let _se = ep.scoped_enable()?;
 			let is_axxon_card = ox16pci954_busses.contains(&ep.bus);
 			if !is_axxon_card {
-				warn!("PCI {}: Found OX16PCI954 device, but not behind an Axxon PCIe-to-PCI bridge", ep);
+				warn!("PCI {}: Found OX16PCI954 device, but not behind an Axxon PCIe-to-PCI bridge", loc);
 			} else {
-				info!("PCI {}: Found OX16PCI954 device on Axxon card", ep);
+				info!("PCI {}: Found OX16PCI954 device on Axxon card", loc);
 			}
 
-			if let Some(driver) = ep.driver()? {
+			if let Some(driver) = info.driver() {
 				if !is_axxon_card {
-					warn!("PCI {}: Not checking flash, as OX16PCI954 is in use by driver {} (and this is not an Axxon card)", ep, driver);
+					warn!("PCI {}: Not checking flash, as OX16PCI954 is in use by driver {} (and this is not an Axxon card)", loc, driver);
 					continue;
 				} else if ep.slot_function.function() == 0 {
-					info!("PCI {}: Not checking flash on function 0 as it is in use by driver {} (function 1 will be using the same flash though)", ep, driver);
+					info!("PCI {}: Not checking flash on function 0 as it is in use by driver {} (function 1 will be using the same flash though)", loc, driver);
 					let mut ep_f1 = ep;
 					ep_f1.slot_function.0 += 1;
 					ox16pci954_check_f1.insert(ep_f1);
 					continue;
 				} else {
 					// there shouldn't be any driver on function 1, as UARTs are only on function 0, and function 1 should be disabled on Axxon cards
-					warn!("PCI {}: In use by driver {}, but shouldn't: the device function isn't wired. Unbinding driver.", ep, driver);
+					warn!("PCI {}: In use by driver {}, but shouldn't: the device function isn't wired. Unbinding driver.", loc, driver);
 					driver.unbind(ep)?;
 				}
 			}
 			ox16pci954_check_f1.remove(&ep);
 
-			let mut ee = ox16_pci954::open_eeprom(ep)?;
-			let image = ox16_pci954::read_flash_program(&mut ee)?;
-			if image != &ox16_pci954::IMAGE[..] {
-				info!("PCI {}: OX16PCI954 image not up to date", ep);
-				if flash_devices {
-					if let Err(e) = ox16_pci954::flash_program(&mut ee, &ox16_pci954::IMAGE) {
-						error!("PCI {}: Failed to flash OX16PCI954 image: {}", ep, e);
-						bail!("Failed to flash");
-					}
-				} else {
-					need_flashing = true;
-				}
-			} else {
-				info!("PCI {}: OX16PCI954 image up to date", ep);
+			let pins = serial::Statistics::new(ox16_pci954::open_eeprom_pins(ep, delay)?);
+			let mut ee = serial::Microwire::detect(pins).map_err(|e| format_err!("PCI {}: EEPROM: {}", loc, e))?;
+			need_flashing |= check_ox16pci954(&format!("PCI {}", loc), &mut ee, flash_devices, reads)?;
+			if show_stats {
+				log_stats(&format!("PCI {}", loc), &ee.into_inner());
 			}
 		}
 	}
@@ -0,0 +1,140 @@
+// Common interface for the EEPROMs on a card
+//
+// Implemented by `serial::Microwire` (OX16PCI954, 8- or 16-bit words) and
+// `axxon::Flash` (PEX8112, bytes); contents are always accessed from
+// address 0, with bytes stored in the low bits of a word.
+
+use std::fmt;
+
+/// Size of an EEPROM
+///
+/// `axxon::Flash` can only detect the address width: unless its size was
+/// set, this is the smallest part with that width, and reading or erasing
+/// all contents fails.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Capacity {
+	pub words: usize,
+	pub word_bits: usize,
+}
+
+impl Capacity {
+	/// content of an erased word (all bits set)
+	pub fn blank_word(&self) -> u16 {
+		(!0u32 >> (32 - self.word_bits)) as u16
+	}
+
+	pub fn bytes(&self) -> usize {
+		self.words * self.word_bits / 8
+	}
+}
+
+impl fmt::Display for Capacity {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} x {}-bit", self.words, self.word_bits)
+	}
+}
+
+pub trait Eeprom {
+	fn capacity(&self) -> Capacity;
+
+	/// read all words
+	fn read_contents(&mut self) -> crate::AResult<Vec<u16>>;
+
+	/// write `data` starting at address 0; following words are kept
+	fn write_contents(&mut self, data: &[u16]) -> crate::AResult<()>;
+
+	/// set all words to `Capacity::blank_word`
+	fn erase_contents(&mut self) -> crate::AResult<()>;
+
+	/// compare the first words with `data`
+	fn verify_contents(&mut self, data: &[u16]) -> crate::AResult<()> {
+		let capacity = self.capacity();
+		ensure!(data.len() <= capacity.words, "{} words don't fit into EEPROM ({})", data.len(), capacity);
+		let contents = self.read_contents()?;
+		let mismatches: Vec<usize> = (0..data.len()).filter(|&address| contents[address] != data[address]).collect();
+		if let Some(&address) = mismatches.first() {
+			bail!("Verify failed for {} words (first at {:02x}: expected {:04x}, EEPROM has {:04x})",
+				mismatches.len(), address, data[address], contents[address]);
+		}
+		Ok(())
+	}
+}
+
+impl<E: Eeprom + ?Sized> Eeprom for &mut E {
+	fn capacity(&self) -> Capacity {
+		(**self).capacity()
+	}
+
+	fn read_contents(&mut self) -> crate::AResult<Vec<u16>> {
+		(**self).read_contents()
+	}
+
+	fn write_contents(&mut self, data: &[u16]) -> crate::AResult<()> {
+		(**self).write_contents(data)
+	}
+
+	fn erase_contents(&mut self) -> crate::AResult<()> {
+		(**self).erase_contents()
+	}
+
+	fn verify_contents(&mut self, data: &[u16]) -> crate::AResult<()> {
+		(**self).verify_contents(data)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::fs;
+
+	use super::Eeprom;
+	use crate::axxon::{
+		FileBridge,
+		open_flash_recovery,
+	};
+	use crate::serial::{
+		Microwire,
+		Organization,
+		Part,
+		Simulator,
+	};
+
+	fn roundtrip(ee: &mut dyn Eeprom) {
+		let capacity = ee.capacity();
+		let data: Vec<u16> = (0..8).map(|i| i * 0x1111 & capacity.blank_word()).collect();
+		ee.erase_contents().unwrap();
+		assert!(ee.read_contents().unwrap().iter().all(|&w| w == capacity.blank_word()));
+		ee.write_contents(&data).unwrap();
+		ee.verify_contents(&data).unwrap();
+		assert!(ee.verify_contents(&[0x0001]).is_err());
+		assert_eq!(ee.read_contents().unwrap().len(), capacity.words);
+	}
+
+	#[test]
+	fn microwire() {
+		for &organization in &[Organization::X8, Organization::X16] {
+			let geometry = Part::C56.geometry_with_organization(organization);
+			let mut ee = Microwire::new(Simulator::new(geometry), geometry);
+			assert_eq!(ee.capacity().bytes(), 256);
+			roundtrip(&mut ee);
+			ee.into_inner().check().unwrap();
+		}
+	}
+
+	#[test]
+	fn flash() {
+		let path = std::env::temp_dir().join(format!("eeprom-roundtrip-{}.bin", std::process::id()));
+		fs::write(&path, vec![0xff; 0x100]).unwrap();
+		{
+			let mut bridge = FileBridge::open(&path).unwrap();
+			let mut flash = open_flash_recovery(&mut bridge).unwrap();
+			// 25xx010 or 25xx020
+			assert_eq!(flash.capacity().words, 0x80);
+			assert!(flash.erase_contents().is_err());
+			assert!(flash.set_size(0x200).is_err());
+			flash.set_size(0x100).unwrap();
+			assert_eq!(flash.capacity().bytes(), 0x100);
+			roundtrip(&mut flash);
+		}
+		fs::remove_file(&path).unwrap();
+	}
+}
@@ -25,9 +25,11 @@ macro_rules! with_context {
 pub type AResult<T> = Result<T, failure::Error>;
 
 pub mod axxon;
+pub mod eeprom;
 pub mod serial;
 pub mod ox16_pci954;
 pub mod pci;
+pub mod signal;
 
 pub fn with_configspace_dev<F, R>(ep: pci::PciEndpoint, f: F) -> AResult<R>
 where
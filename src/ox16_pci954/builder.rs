@@ -0,0 +1,178 @@
+// Build an EEPROM program from settings instead of raw words
+//
+// Settings update bit fields of register bytes; every touched byte gets
+// written once (in register order), with the bits not set through the
+// builder being zero.
+
+use std::collections::BTreeMap;
+
+use super::local_configuration_types::{
+	MioConfiguration,
+	Mode,
+	PowerDownFilterTime,
+};
+use super::program::{
+	FunctionConfig,
+	Program,
+	RegisterWrite,
+};
+
+// local configuration register bytes
+const LCC_0: u8 = 0x00;
+const MIC_0: u8 = 0x04;
+const GIS_2: u8 = 0x1e;
+
+// PCI configuration space bytes
+const DEVICE_ID_LOW: u8 = 0x02;
+const SUBSYSTEM_VENDOR_ID: u8 = 0x2c;
+const SUBSYSTEM_ID: u8 = 0x2e;
+const INTERRUPT_PIN: u8 = 0x3d;
+
+fn set_bits(registers: &mut BTreeMap<u8, u8>, register: u8, mask: u8, value: u8) {
+	let byte = registers.entry(register).or_insert(0);
+	*byte = (*byte & !mask) | (value & mask);
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct ProgramBuilder {
+	local: BTreeMap<u8, u8>,
+	identification: Vec<u16>,
+	// by function
+	config: [BTreeMap<u8, u8>; 2],
+}
+
+impl ProgramBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn function_config(&mut self, function: u8) -> &mut BTreeMap<u8, u8> {
+		assert!(function <= 1, "PCI function {} out of range", function);
+		&mut self.config[usize::from(function)]
+	}
+
+	/// LCC[1:0]
+	pub fn mode(&mut self, mode: Mode) -> &mut Self {
+		set_bits(&mut self.local, LCC_0, 0x03, mode.to_bits());
+		self
+	}
+
+	/// LCC[6:5]
+	pub fn power_down_filter_time(&mut self, time: PowerDownFilterTime) -> &mut Self {
+		set_bits(&mut self.local, LCC_0, 0x60, time.to_bits() << 5);
+		self
+	}
+
+	/// Configure MIO pins (0..=11) in MIC
+	pub fn mio<I: IntoIterator<Item = usize>>(&mut self, pins: I, config: MioConfiguration) -> &mut Self {
+		for pin in pins {
+			assert!(pin < 12, "MIO pin {} out of range", pin);
+			let shift = (pin % 4) * 2;
+			set_bits(&mut self.local, MIC_0 + (pin / 4) as u8, 0x03 << shift, config.to_bits() << shift);
+		}
+		self
+	}
+
+	/// GIS[19:16]; `true` enables the interrupt of a UART
+	pub fn uart_interrupt_mask(&mut self, mask: [bool; 4]) -> &mut Self {
+		for (uart, &enabled) in mask.iter().enumerate() {
+			set_bits(&mut self.local, GIS_2, 1 << uart, (enabled as u8) << uart);
+		}
+		self
+	}
+
+	/// GIS[31:20]; `true` enables the interrupt of a MIO pin
+	pub fn mio_interrupt_mask(&mut self, mask: [bool; 12]) -> &mut Self {
+		for (pin, &enabled) in mask.iter().enumerate() {
+			let bit = pin + 4;
+			set_bits(&mut self.local, GIS_2 + (bit / 8) as u8, 1 << (bit % 8), (enabled as u8) << (bit % 8));
+		}
+		self
+	}
+
+	/// Write a local configuration register byte directly
+	pub fn local_register(&mut self, register: u8, value: u8) -> &mut Self {
+		self.local.insert(register, value);
+		self
+	}
+
+	/// Zone 2 words (15 bits each)
+	pub fn identification(&mut self, words: &[u16]) -> &mut Self {
+		self.identification.extend_from_slice(words);
+		self
+	}
+
+	/// Write a PCI configuration space byte of `function` (0 or 1)
+	pub fn config_register(&mut self, function: u8, register: u8, value: u8) -> &mut Self {
+		self.function_config(function).insert(register, value);
+		self
+	}
+
+	/// Subsystem vendor and subsystem id of `function`
+	pub fn subsystem_ids(&mut self, function: u8, vendor: u16, device: u16) -> &mut Self {
+		let config = self.function_config(function);
+		config.insert(SUBSYSTEM_VENDOR_ID, vendor as u8);
+		config.insert(SUBSYSTEM_VENDOR_ID + 1, (vendor >> 8) as u8);
+		config.insert(SUBSYSTEM_ID, device as u8);
+		config.insert(SUBSYSTEM_ID + 1, (device >> 8) as u8);
+		self
+	}
+
+	/// Hide function 1 like the vendor flash tool does: clear the low byte
+	/// of the device id and the interrupt pin
+	pub fn disable_function1(&mut self) -> &mut Self {
+		self.config_register(1, DEVICE_ID_LOW, 0x00);
+		self.config_register(1, INTERRUPT_PIN, 0x00)
+	}
+
+	pub fn program(&self) -> Program {
+		fn writes(registers: &BTreeMap<u8, u8>) -> Vec<RegisterWrite> {
+			registers.iter().map(|(&register, &value)| RegisterWrite { register, value }).collect()
+		}
+
+		let functions: Vec<FunctionConfig> = self.config.iter().enumerate()
+			.filter(|(_, config)| !config.is_empty())
+			.map(|(function, config)| FunctionConfig { function: function as u8, writes: writes(config) })
+			.collect();
+		Program {
+			local_configuration: writes(&self.local),
+			identification: self.identification.clone(),
+			functions: if functions.is_empty() { None } else { Some(functions) },
+		}
+	}
+
+	/// EEPROM words; fails for registers or identification words that don't
+	/// fit into their bit fields
+	pub fn build(&self) -> crate::AResult<Vec<u16>> {
+		self.program().serialize()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::ProgramBuilder;
+	use crate::ox16_pci954::IMAGE;
+	use crate::ox16_pci954::local_configuration_types::MioConfiguration;
+
+	#[test]
+	fn build_image() {
+		let words = ProgramBuilder::new()
+			.mio(0..12, MioConfiguration::OutputOne)
+			.uart_interrupt_mask([true; 4])
+			.mio_interrupt_mask([false; 12])
+			.disable_function1()
+			.build()
+			.unwrap();
+		assert_eq!(words, &IMAGE[..]);
+	}
+
+	#[test]
+	fn subsystem_ids() {
+		let words = ProgramBuilder::new()
+			.subsystem_ids(0, 0x1415, 0x0001)
+			.build()
+			.unwrap();
+		assert_eq!(words, &[0x9501, 0x8000, 0xac15, 0xad14, 0xae01, 0x2f00, 0x0000]);
+		assert!(ProgramBuilder::new().local_register(0x80, 0).build().is_err());
+	}
+}
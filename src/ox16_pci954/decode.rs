@@ -1,5 +1,6 @@
 /// Decode Local configuration registers
 
+use std::fmt;
 use std::io;
 
 use crate::pci::{
@@ -17,6 +18,18 @@ pub mod local_configuration_types {
 		ThirtyTwoBitLocalBus,
 	}
 
+	impl Mode {
+		/// LCC[1:0]
+		pub fn to_bits(self) -> u8 {
+			match self {
+				Mode::UartAndEightBitLocalBus => 0b00,
+				Mode::UartAndParallelPort => 0b01,
+				Mode::UartAndSubsystemIDs => 0b10,
+				Mode::ThirtyTwoBitLocalBus => 0b11,
+			}
+		}
+	}
+
 	// 8-bit local bus: which part of a dword
 	#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 	pub enum EndianByteLane {
@@ -34,6 +47,18 @@ pub mod local_configuration_types {
 		Wait518Seconds,
 	}
 
+	impl PowerDownFilterTime {
+		/// LCC[6:5]
+		pub fn to_bits(self) -> u8 {
+			match self {
+				PowerDownFilterTime::Disabled => 0b00,
+				PowerDownFilterTime::Wait4Seconds => 0b01,
+				PowerDownFilterTime::Wait129Seconds => 0b10,
+				PowerDownFilterTime::Wait518Seconds => 0b11,
+			}
+		}
+	}
+
 	// Multi-purpose IO configuration
 	#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 	pub enum MioConfiguration {
@@ -53,6 +78,15 @@ pub mod local_configuration_types {
 				_ => unreachable!(),
 			}
 		}
+
+		pub fn to_bits(self) -> u8 {
+			match self {
+				MioConfiguration::NonInvertingInput => 0b00,
+				MioConfiguration::InvertingInput => 0b01,
+				MioConfiguration::OutputZero => 0b10,
+				MioConfiguration::OutputOne => 0b11,
+			}
+		}
 	}
 
 	#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -128,6 +162,11 @@ pub fn decode_resource3(ep: PciEndpoint) -> io::Result<LocalConfiguration> {
 		r3.read_slice(0, &mut buf[..]);
 	}
 
+	Ok(decode_bytes(&buf))
+}
+
+/// Decode the 32 local configuration register bytes
+pub fn decode_bytes(buf: &[u8; 32]) -> LocalConfiguration {
 	// byte 0:
 	let mode = match buf[0x00] & 0x03 {
 		0b00 => Mode::UartAndEightBitLocalBus,
@@ -352,7 +391,7 @@ pub fn decode_resource3(ep: PciEndpoint) -> io::Result<LocalConfiguration> {
 		0 != (buf[0x1f] & 0x80),
 	];
 
-	Ok(LocalConfiguration{
+	LocalConfiguration{
 		mode,
 		uart_clock_output,
 		endian_byte_lane,
@@ -400,5 +439,241 @@ pub fn decode_resource3(ep: PciEndpoint) -> io::Result<LocalConfiguration> {
 		mio_state,
 		uart_interrupt_mask,
 		mio_mask,
-	})
+	}
+}
+
+macro_rules! getters {
+	($($name:ident: $ty:ty,)*) => {
+		impl LocalConfiguration {
+			$(
+				pub fn $name(&self) -> $ty {
+					self.$name
+				}
+			)*
+		}
+	};
+}
+
+getters! {
+	mode: Mode,
+	uart_clock_output: bool,
+	endian_byte_lane: EndianByteLane,
+	power_down_filter_time: PowerDownFilterTime,
+	function1_mio2_pme_enable: bool,
+	eeprom_data_in: bool,
+	eeprom_valid: bool,
+	eeprom_reload_in_progress: bool,
+	mio0_config: Option<MioConfiguration>,
+	mio1_config: Option<MioConfiguration>,
+	mio2_config: MioConfigurationOrPME,
+	mio3_config: MioConfiguration,
+	mio4_config: MioConfiguration,
+	mio5_config: MioConfiguration,
+	mio6_config: MioConfiguration,
+	mio7_config: MioConfiguration,
+	mio8_config: MioConfiguration,
+	mio9_config: MioConfiguration,
+	mio10_config: MioConfiguration,
+	mio11_config: MioConfiguration,
+	local_bus_read_chip_select_assertion: u8,
+	local_bus_read_chip_select_deassertion: u8,
+	local_bus_write_chip_select_assertion: u8,
+	local_bus_write_chip_select_deassertion: u8,
+	local_bus_read_control_assertion: u8,
+	local_bus_read_control_deassertion: u8,
+	local_bus_write_control_assertion: u8,
+	local_bus_write_control_deassertion: u8,
+	local_bus_write_data_bus_control_assertion: u8,
+	local_bus_write_data_bus_control_deassertion: u8,
+	local_bus_read_data_bus_control_assertion: u8,
+	local_bus_read_data_bus_control_deassertion: u8,
+	function1_bar0_block_size: u8,
+	local_bus_lower_address_cs_decode: u8,
+	function1_bar1_block_size: Option<u8>,
+	local_bus_software_reset: bool,
+	local_bus_clock_enable: bool,
+	local_bus_interface_type: bool,
+	uart_receiver_levels: [u8; 4],
+	uart_transmitter_levels: [u8; 4],
+	uart_interrupt_source: [u8; 4],
+	uart_good_status: [bool; 4],
+	uart_global_good_status: bool,
+	uart_interrupt_state: [bool; 4],
+	mio_state: [bool; 12],
+	uart_interrupt_mask: [bool; 4],
+	mio_mask: [bool; 12],
+}
+
+impl LocalConfiguration {
+	/// MIO 0..11; None if the pin is used for something else (parallel port,
+	/// power-down output)
+	pub fn mio_configs(&self) -> [Option<MioConfigurationOrPME>; 12] {
+		use self::MioConfigurationOrPME::MioConfiguration as Config;
+		[
+			self.mio0_config.map(Config),
+			self.mio1_config.map(Config),
+			Some(self.mio2_config),
+			Some(Config(self.mio3_config)),
+			Some(Config(self.mio4_config)),
+			Some(Config(self.mio5_config)),
+			Some(Config(self.mio6_config)),
+			Some(Config(self.mio7_config)),
+			Some(Config(self.mio8_config)),
+			Some(Config(self.mio9_config)),
+			Some(Config(self.mio10_config)),
+			Some(Config(self.mio11_config)),
+		]
+	}
+
+	// (assertion, deassertion) of the local bus timings with their names
+	fn local_bus_timings(&self) -> [(&'static str, u8, u8); 6] {
+		[
+			("read_chip_select", self.local_bus_read_chip_select_assertion, self.local_bus_read_chip_select_deassertion),
+			("write_chip_select", self.local_bus_write_chip_select_assertion, self.local_bus_write_chip_select_deassertion),
+			("read_control", self.local_bus_read_control_assertion, self.local_bus_read_control_deassertion),
+			("write_control", self.local_bus_write_control_assertion, self.local_bus_write_control_deassertion),
+			("write_data_bus", self.local_bus_write_data_bus_control_assertion, self.local_bus_write_data_bus_control_deassertion),
+			("read_data_bus", self.local_bus_read_data_bus_control_assertion, self.local_bus_read_data_bus_control_deassertion),
+		]
+	}
+
+	/// JSON object with the same groups as the `Display` output; enums are
+	/// strings with the variant names
+	pub fn to_json(&self) -> String {
+		fn list<T: fmt::Display>(items: &[T]) -> String {
+			let items: Vec<String> = items.iter().map(ToString::to_string).collect();
+			format!("[{}]", items.join(", "))
+		}
+		fn string<T: fmt::Debug>(value: T) -> String {
+			format!("\"{:?}\"", value)
+		}
+		fn optional<T: fmt::Display>(value: Option<T>) -> String {
+			value.map_or_else(|| "null".to_string(), |value| value.to_string())
+		}
+
+		let mio: Vec<String> = self.mio_configs().iter().map(|config| match *config {
+			None => "null".to_string(),
+			Some(MioConfigurationOrPME::PME(_)) => string("PME"),
+			Some(MioConfigurationOrPME::MioConfiguration(config)) => string(config),
+		}).collect();
+		let mio2_pme_enabled = match self.mio2_config {
+			MioConfigurationOrPME::PME(enabled) => Some(enabled),
+			MioConfigurationOrPME::MioConfiguration(_) => None,
+		};
+		let timings: Vec<String> = self.local_bus_timings().iter()
+			.map(|&(name, assertion, deassertion)| format!("\"{}\": {{\"assertion\": {}, \"deassertion\": {}}}", name, assertion, deassertion))
+			.collect();
+
+		let mut json = String::from("{\n");
+		json += &format!("  \"lcc\": {{\"mode\": {}, \"uart_clock_output\": {}, \"endian_byte_lane\": {}, \"power_down_filter_time\": {}, \"function1_mio2_pme_enable\": {}, \"eeprom_data_in\": {}, \"eeprom_valid\": {}, \"eeprom_reload_in_progress\": {}}},\n",
+			string(self.mode), self.uart_clock_output, string(self.endian_byte_lane), string(self.power_down_filter_time),
+			self.function1_mio2_pme_enable, self.eeprom_data_in, self.eeprom_valid, self.eeprom_reload_in_progress);
+		json += &format!("  \"mic\": {{\"mio\": {}, \"mio2_pme_enabled\": {}}},\n", list(&mio), optional(mio2_pme_enabled));
+		json += &format!("  \"local_bus\": {{{}, \"function1_bar0_block_size\": {}, \"lower_address_cs_decode\": {}, \"function1_bar1_block_size\": {}, \"software_reset\": {}, \"clock_enable\": {}, \"interface_type\": {}}},\n",
+			timings.join(", "), self.function1_bar0_block_size, self.local_bus_lower_address_cs_decode, optional(self.function1_bar1_block_size),
+			self.local_bus_software_reset, self.local_bus_clock_enable, self.local_bus_interface_type);
+		json += &format!("  \"fifo_levels\": {{\"uart_receiver\": {}, \"uart_transmitter\": {}}},\n",
+			list(&self.uart_receiver_levels), list(&self.uart_transmitter_levels));
+		json += &format!("  \"interrupts\": {{\"uart_source\": {}, \"uart_good_status\": {}, \"uart_global_good_status\": {}, \"uart_state\": {}, \"uart_mask\": {}, \"mio_state\": {}, \"mio_mask\": {}}}\n",
+			list(&self.uart_interrupt_source), list(&self.uart_good_status), self.uart_global_good_status,
+			list(&self.uart_interrupt_state), list(&self.uart_interrupt_mask), list(&self.mio_state), list(&self.mio_mask));
+		json += "}";
+		json
+	}
+}
+
+fn yes_no(value: bool) -> &'static str {
+	if value { "yes" } else { "no" }
+}
+
+// MIO 11..0 as bits
+fn mio_bits(bits: &[bool; 12]) -> String {
+	bits.iter().rev().map(|&bit| if bit { '1' } else { '0' }).collect()
+}
+
+impl fmt::Display for LocalConfiguration {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Local configuration and control (LCC):")?;
+		writeln!(f, "  mode: {:?}", self.mode)?;
+		writeln!(f, "  UART clock output: {}", yes_no(self.uart_clock_output))?;
+		writeln!(f, "  endian byte lane: {:?}", self.endian_byte_lane)?;
+		writeln!(f, "  power-down filter time: {:?}", self.power_down_filter_time)?;
+		writeln!(f, "  function 1 MIO2 PME enable: {}", yes_no(self.function1_mio2_pme_enable))?;
+		writeln!(f, "  EEPROM: valid {}, data in {}, reload in progress {}",
+			yes_no(self.eeprom_valid), self.eeprom_data_in as u8, yes_no(self.eeprom_reload_in_progress))?;
+
+		writeln!(f, "Multi-purpose IO configuration (MIC):")?;
+		for (pin, config) in self.mio_configs().iter().enumerate() {
+			match *config {
+				None => writeln!(f, "  MIO{}: not available", pin)?,
+				Some(MioConfigurationOrPME::PME(enabled)) => writeln!(f, "  MIO{}: PME (enabled: {})", pin, yes_no(enabled))?,
+				Some(MioConfigurationOrPME::MioConfiguration(config)) => writeln!(f, "  MIO{}: {:?}", pin, config)?,
+			}
+		}
+
+		writeln!(f, "Local bus timing (LT1, LT2):")?;
+		for &(name, assertion, deassertion) in &self.local_bus_timings() {
+			writeln!(f, "  {}: assertion {}, deassertion {}", name.replace('_', " "), assertion, deassertion)?;
+		}
+		writeln!(f, "  function 1 BAR0 block size: {}", self.function1_bar0_block_size)?;
+		writeln!(f, "  lower address CS decode: {}", self.local_bus_lower_address_cs_decode)?;
+		match self.function1_bar1_block_size {
+			None => writeln!(f, "  function 1 BAR1 block size: not used")?,
+			Some(size) => writeln!(f, "  function 1 BAR1 block size: {}", size)?,
+		}
+		writeln!(f, "  software reset {}, clock enable {}, interface type {}",
+			yes_no(self.local_bus_software_reset), yes_no(self.local_bus_clock_enable), self.local_bus_interface_type as u8)?;
+
+		writeln!(f, "FIFO levels (URL, UTL):")?;
+		for uart in 0..4 {
+			writeln!(f, "  UART{}: receiver {}, transmitter {}", uart, self.uart_receiver_levels[uart], self.uart_transmitter_levels[uart])?;
+		}
+
+		writeln!(f, "Interrupts (UIS, GIS):")?;
+		for uart in 0..4 {
+			writeln!(f, "  UART{}: source 0x{:02x}, good status {}, pending {}, enabled {}", uart,
+				self.uart_interrupt_source[uart], yes_no(self.uart_good_status[uart]),
+				yes_no(self.uart_interrupt_state[uart]), yes_no(self.uart_interrupt_mask[uart]))?;
+		}
+		writeln!(f, "  UART global good status: {}", yes_no(self.uart_global_good_status))?;
+		writeln!(f, "  MIO 11..0 state: {}", mio_bits(&self.mio_state))?;
+		write!(f, "  MIO 11..0 interrupt enabled: {}", mio_bits(&self.mio_mask))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::decode_bytes;
+	use super::local_configuration_types::*;
+
+	#[test]
+	fn display_and_json() {
+		let mut buf = [0u8; 32];
+		// UART + parallel port, 4s power-down filter; MIO3 output high
+		buf[0x00] = 0b0010_0001;
+		buf[0x04] = 0b1100_0000;
+		buf[0x10] = 0x20;
+		buf[0x1e] = 0x0f;
+		buf[0x1f] = 0x80;
+		let config = decode_bytes(&buf);
+		assert_eq!(config.mode(), Mode::UartAndParallelPort);
+		assert_eq!(config.power_down_filter_time(), PowerDownFilterTime::Wait4Seconds);
+		assert_eq!(config.mio0_config(), None);
+		assert_eq!(config.mio3_config(), MioConfiguration::OutputOne);
+		assert_eq!(config.uart_interrupt_mask(), [true; 4]);
+
+		let text = config.to_string();
+		assert!(text.contains("\n  MIO0: not available\n"));
+		assert!(text.contains("\n  UART0: receiver 32, transmitter 0\n"));
+		assert!(text.ends_with("interrupt enabled: 100000000000"));
+
+		let json = config.to_json();
+		assert!(json.starts_with("{\n  \"lcc\": {\"mode\": \"UartAndParallelPort\", "));
+		assert!(json.contains("\"mio\": [null, null, \"NonInvertingInput\", \"OutputOne\", "));
+		assert!(json.contains("\"mio2_pme_enabled\": null"));
+		assert!(json.contains("\"uart_receiver\": [32, 0, 0, 0]"));
+		assert!(json.contains("\"read_chip_select\": {\"assertion\": 0, \"deassertion\": 0}"));
+		// balanced (no strings contain braces)
+		assert_eq!(json.matches('{').count(), json.matches('}').count());
+	}
 }
@@ -0,0 +1,170 @@
+// Annotated listing of an EEPROM program, one line per word
+
+use std::fmt;
+
+use super::program::{
+	Program,
+	ProgramError,
+	RegisterWrite,
+};
+
+/// Name and bit range of a local configuration register byte, with a short
+/// description of its fields
+pub fn local_register_name(register: u8) -> Option<(&'static str, &'static str)> {
+	Some(match register {
+		0x00 => ("LCC[7:0]", "mode, UART clock out, endian byte lane, power-down filter, F1 MIO2 PME"),
+		0x01 => ("LCC[15:8]", "reserved"),
+		0x02 => ("LCC[23:16]", "reserved"),
+		0x03 => ("LCC[31:24]", "EEPROM control"),
+		0x04 => ("MIC[7:0]", "MIO 3..0 configuration"),
+		0x05 => ("MIC[15:8]", "MIO 7..4 configuration"),
+		0x06 => ("MIC[23:16]", "MIO 11..8 configuration"),
+		0x07 => ("MIC[31:24]", "reserved"),
+		0x08 => ("LT1[7:0]", "read chip-select (de-)assertion"),
+		0x09 => ("LT1[15:8]", "write chip-select (de-)assertion"),
+		0x0a => ("LT1[23:16]", "read control (de-)assertion"),
+		0x0b => ("LT1[31:24]", "write control (de-)assertion"),
+		0x0c => ("LT2[7:0]", "write data bus (de-)assertion"),
+		0x0d => ("LT2[15:8]", "read data bus (de-)assertion"),
+		0x0e => ("LT2[23:16]", "F1 BAR0 block size, lower address CS decode"),
+		0x0f => ("LT2[31:24]", "lower address CS decode, F1 BAR1 block size, local bus reset/clock/interface"),
+		0x10..=0x13 => (["URL[7:0]", "URL[15:8]", "URL[23:16]", "URL[31:24]"][usize::from(register - 0x10)], "UART receiver FIFO level"),
+		0x14..=0x17 => (["UTL[7:0]", "UTL[15:8]", "UTL[23:16]", "UTL[31:24]"][usize::from(register - 0x14)], "UART transmitter FIFO level"),
+		0x18 => ("UIS[7:0]", "UART interrupt source"),
+		0x19 => ("UIS[15:8]", "UART interrupt source"),
+		0x1a => ("UIS[23:16]", "UART interrupt source"),
+		0x1b => ("UIS[31:24]", "UART good status"),
+		0x1c => ("GIS[7:0]", "UART interrupt state, MIO 3..0 state"),
+		0x1d => ("GIS[15:8]", "MIO 11..4 state"),
+		0x1e => ("GIS[23:16]", "UART interrupt mask, MIO 3..0 interrupt mask"),
+		0x1f => ("GIS[31:24]", "MIO 11..4 interrupt mask"),
+		_ => return None,
+	})
+}
+
+/// Name of a byte in the PCI configuration space header (type 0)
+pub fn config_register_name(register: u8) -> Option<&'static str> {
+	const BAR_BYTES: [&str; 24] = [
+		"BAR0 byte 0", "BAR0 byte 1", "BAR0 byte 2", "BAR0 byte 3",
+		"BAR1 byte 0", "BAR1 byte 1", "BAR1 byte 2", "BAR1 byte 3",
+		"BAR2 byte 0", "BAR2 byte 1", "BAR2 byte 2", "BAR2 byte 3",
+		"BAR3 byte 0", "BAR3 byte 1", "BAR3 byte 2", "BAR3 byte 3",
+		"BAR4 byte 0", "BAR4 byte 1", "BAR4 byte 2", "BAR4 byte 3",
+		"BAR5 byte 0", "BAR5 byte 1", "BAR5 byte 2", "BAR5 byte 3",
+	];
+	Some(match register {
+		0x00 => "vendor id low",
+		0x01 => "vendor id high",
+		0x02 => "device id low",
+		0x03 => "device id high",
+		0x04 => "command low",
+		0x05 => "command high",
+		0x06 => "status low",
+		0x07 => "status high",
+		0x08 => "revision id",
+		0x09 => "programming interface",
+		0x0a => "subclass",
+		0x0b => "class code",
+		0x0c => "cache line size",
+		0x0d => "latency timer",
+		0x0e => "header type",
+		0x0f => "BIST",
+		0x10..=0x27 => BAR_BYTES[usize::from(register - 0x10)],
+		0x2c => "subsystem vendor id low",
+		0x2d => "subsystem vendor id high",
+		0x2e => "subsystem id low",
+		0x2f => "subsystem id high",
+		0x34 => "capabilities pointer",
+		0x3c => "interrupt line",
+		0x3d => "interrupt pin",
+		0x3e => "min grant",
+		0x3f => "max latency",
+		_ => return None,
+	})
+}
+
+/// A word of the program with its meaning
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DisassembledWord {
+	pub offset: usize,
+	pub word: u16,
+	pub zone: usize,
+	pub text: String,
+}
+
+impl fmt::Display for DisassembledWord {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "@{:02x}  zone {}  0x{:04x}: {}", self.offset, self.zone, self.word, self.text)
+	}
+}
+
+fn local_write(write: &RegisterWrite) -> String {
+	match local_register_name(write.register) {
+		None => format!("LCR[0x{:02x}] = 0x{:02x} (unknown register)", write.register, write.value),
+		Some((name, description)) => format!("LCR[0x{:02x}] {} = 0x{:02x} ({})", write.register, name, write.value, description),
+	}
+}
+
+fn config_write(function: u8, write: &RegisterWrite) -> String {
+	match config_register_name(write.register) {
+		None => format!("F{} cfg[0x{:02x}] = 0x{:02x}", function, write.register, write.value),
+		Some(name) => format!("F{} cfg[0x{:02x}] {} = 0x{:02x}", function, write.register, name, write.value),
+	}
+}
+
+/// Annotate the program at the start of `words`
+pub fn disassemble(words: &[u16]) -> Result<Vec<DisassembledWord>, ProgramError> {
+	let program = Program::parse(words)?;
+	let mut lines = Vec::new();
+	// serializing gives the words back, so the offsets match `words`
+	let mut push = |zone: usize, text: String| {
+		let offset = lines.len();
+		lines.push(DisassembledWord { offset, word: words[offset], zone, text });
+	};
+
+	let header = program.header();
+	let zones: Vec<&str> = [(header.zone1, "1"), (header.zone2, "2"), (header.zone3, "3")].iter()
+		.filter(|&&(present, _)| present)
+		.map(|&(_, zone)| zone)
+		.collect();
+	if zones.is_empty() {
+		push(0, "header, no zones".to_string());
+	} else {
+		push(0, format!("header, zones {}", zones.join(" ")));
+	}
+
+	for write in &program.local_configuration {
+		push(1, local_write(write));
+	}
+	for &word in &program.identification {
+		push(2, format!("identification 0x{:04x}", word));
+	}
+	if let Some(ref functions) = program.functions {
+		for function in functions {
+			push(3, format!("configure function {}", function.function));
+			for write in &function.writes {
+				push(3, config_write(function.function, write));
+			}
+		}
+		push(3, "end of zone 3".to_string());
+	}
+
+	Ok(lines)
+}
+
+#[cfg(test)]
+mod test {
+	use super::disassemble;
+	use crate::ox16_pci954::IMAGE;
+
+	#[test]
+	fn disassemble_image() {
+		let lines: Vec<String> = disassemble(&IMAGE).unwrap().iter().map(|line| line.to_string()).collect();
+		assert_eq!(lines.len(), IMAGE.len());
+		assert_eq!(lines[0], "@00  zone 0  0x9505: header, zones 1 3");
+		assert_eq!(lines[1], "@01  zone 1  0x84ff: LCR[0x04] MIC[7:0] = 0xff (MIO 3..0 configuration)");
+		assert_eq!(lines[7], "@07  zone 3  0x8200: F1 cfg[0x02] device id low = 0x00");
+		assert_eq!(lines[9], "@09  zone 3  0x0000: end of zone 3");
+		assert_eq!(disassemble(&IMAGE[..5]).unwrap_err().offset, 5);
+	}
+}
@@ -7,8 +7,12 @@ use crate::pci::{
 };
 
 use crate::serial::{
+	EdgeDelay,
+	Geometry,
 	Hardware,
 	HardwareOperations,
+	Microwire,
+	Organization,
 	OutPins,
 };
 
@@ -17,6 +21,7 @@ where
 	R: PciResource,
 {
 	resource: R,
+	delay: EdgeDelay,
 }
 
 impl<R> Hardware for WrapPciResource<R>
@@ -36,9 +41,44 @@ where
 		// println!("EEPROM in: {:02x}", input);
 		0 != (input & 0x08)
 	}
+
+	fn delay(&mut self) {
+		let resource = &mut self.resource;
+		self.delay.wait(|| {
+			resource.read_byte(3usize);
+		});
+	}
 }
 
-pub fn open_eeprom(ep: PciEndpoint) -> io::Result<impl HardwareOperations> {
+/// Open the pins the EEPROM is connected to
+pub fn open_eeprom_pins(ep: PciEndpoint, delay: EdgeDelay) -> io::Result<impl Hardware> {
 	let resource = open_resource_readwrite(ep, 3)?;
-	Ok(WrapPciResource{resource})
+	Ok(WrapPciResource { resource, delay })
+}
+
+fn detect_eeprom(ep: PciEndpoint, organization: Organization, delay: EdgeDelay) -> crate::AResult<impl HardwareOperations> {
+	with_context!(("PCI {}: EEPROM", ep), {
+		let hardware = open_eeprom_pins(ep, delay)?;
+		Microwire::detect_with_organization(hardware, organization)
+	})
+}
+
+/// Open EEPROM (16-bit organization) and detect its address width
+pub fn open_eeprom(ep: PciEndpoint) -> crate::AResult<impl HardwareOperations> {
+	detect_eeprom(ep, Organization::X16, EdgeDelay::default())
+}
+
+/// Open EEPROM (16-bit organization) with custom timing and detect its address width
+pub fn open_eeprom_with_delay(ep: PciEndpoint, delay: EdgeDelay) -> crate::AResult<impl HardwareOperations> {
+	detect_eeprom(ep, Organization::X16, delay)
+}
+
+/// Open EEPROM and detect its address width
+pub fn open_eeprom_with_organization(ep: PciEndpoint, organization: Organization) -> crate::AResult<impl HardwareOperations> {
+	detect_eeprom(ep, organization, EdgeDelay::default())
+}
+
+pub fn open_eeprom_with_geometry(ep: PciEndpoint, geometry: Geometry) -> io::Result<impl HardwareOperations> {
+	let hardware = open_eeprom_pins(ep, EdgeDelay::default())?;
+	Ok(Microwire::new(hardware, geometry))
 }
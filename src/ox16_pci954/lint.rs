@@ -0,0 +1,181 @@
+// Static checks of an EEPROM program before flashing it
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::program::{
+	Program,
+	RegisterWrite,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Severity {
+	Warning,
+	Error,
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Severity::Warning => write!(f, "warning"),
+			Severity::Error => write!(f, "error"),
+		}
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Lint {
+	pub severity: Severity,
+	/// word offset (None for the program as a whole)
+	pub offset: Option<usize>,
+	pub message: String,
+}
+
+impl fmt::Display for Lint {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.offset {
+			None => write!(f, "{}: {}", self.severity, self.message),
+			Some(offset) => write!(f, "{} @{:02x}: {}", self.severity, offset, self.message),
+		}
+	}
+}
+
+// local configuration register bytes without (writable) fields
+fn local_register_problem(register: u8) -> Option<(Severity, &'static str)> {
+	match register {
+		0x01 | 0x02 | 0x07 => Some((Severity::Error, "reserved local configuration register")),
+		0x03 => Some((Severity::Warning, "local configuration register controls the EEPROM interface")),
+		0x18..=0x1d => Some((Severity::Warning, "local configuration register holds status (read-only)")),
+		0x20..=0x7f => Some((Severity::Error, "no local configuration register at this address")),
+		_ => None,
+	}
+}
+
+// PCI configuration space bytes the EEPROM can't (usefully) set
+fn config_register_problem(register: u8) -> Option<(Severity, &'static str)> {
+	match register {
+		0x06 | 0x07 | 0x0e | 0x0f | 0x34 => Some((Severity::Error, "read-only configuration register")),
+		0x04 | 0x05 | 0x0c | 0x0d | 0x10..=0x27 | 0x3c => Some((Severity::Warning, "configuration register gets set by the host")),
+		_ => None,
+	}
+}
+
+struct Linter {
+	lints: Vec<Lint>,
+	// offset of the first write by (register space, register); across
+	// blocks, as a function may be configured in several
+	seen: HashMap<(String, u8), usize>,
+}
+
+impl Linter {
+	fn push(&mut self, severity: Severity, offset: Option<usize>, message: String) {
+		self.lints.push(Lint { severity, offset, message });
+	}
+
+	// `writes` start at word `offset`
+	fn writes<F>(&mut self, offset: usize, writes: &[RegisterWrite], what: &str, problem: F)
+	where
+		F: Fn(u8) -> Option<(Severity, &'static str)>,
+	{
+		for (index, write) in writes.iter().enumerate() {
+			let offset = offset + index;
+			if let Some((severity, message)) = problem(write.register) {
+				self.push(severity, Some(offset), format!("{}[0x{:02x}]: {}", what, write.register, message));
+			}
+			if let Some(&previous) = self.seen.get(&(what.to_string(), write.register)) {
+				self.push(Severity::Warning, Some(offset), format!("{}[0x{:02x}] already written @{:02x}", what, write.register, previous));
+			} else {
+				self.seen.insert((what.to_string(), write.register), offset);
+			}
+		}
+	}
+}
+
+/// Check the program at the start of `words`; `capacity` is the size of the
+/// EEPROM in words (if known)
+pub fn lint(words: &[u16], capacity: Option<usize>) -> Vec<Lint> {
+	let mut linter = Linter { lints: Vec::new(), seen: HashMap::new() };
+	let program = match Program::parse(words) {
+		Ok(program) => program,
+		Err(e) => {
+			linter.push(Severity::Error, Some(e.offset), e.message);
+			return linter.lints;
+		},
+	};
+
+	// offsets follow the serialized layout (header first)
+	let mut offset = 1;
+	linter.writes(offset, &program.local_configuration, "LCR", local_register_problem);
+	offset += program.local_configuration.len() + program.identification.len();
+
+	let mut function0_device_id: u16 = 0x9501;
+	for function in program.functions.iter().flatten() {
+		offset += 1;
+		let what = format!("F{} cfg", function.function);
+		linter.writes(offset, &function.writes, &what, config_register_problem);
+		if function.function == 0 {
+			for write in &function.writes {
+				match write.register {
+					0x02 => function0_device_id = (function0_device_id & 0xff00) | u16::from(write.value),
+					0x03 => function0_device_id = (function0_device_id & 0x00ff) | u16::from(write.value) << 8,
+					_ => (),
+				}
+			}
+		}
+		offset += function.writes.len();
+	}
+	if function0_device_id == 0x9500 {
+		linter.push(Severity::Error, None, "function 0 would be disabled (device id 9500)".to_string());
+	}
+
+	let len = program.serialize().map_or(words.len(), |words| words.len());
+	if let Some(capacity) = capacity {
+		if len > capacity {
+			linter.push(Severity::Error, None, format!("program has {} words, EEPROM only {}", len, capacity));
+		}
+	}
+
+	linter.lints
+}
+
+#[cfg(test)]
+mod test {
+	use super::{
+		Severity,
+		lint,
+	};
+	use crate::ox16_pci954::{
+		IMAGE,
+		ProgramBuilder,
+	};
+
+	#[test]
+	fn lint_programs() {
+		assert!(lint(&IMAGE, Some(64)).is_empty());
+		assert_eq!(lint(&IMAGE, Some(8))[0].to_string(), "error: program has 10 words, EEPROM only 8");
+
+		// missing zone 3 terminator
+		let lints = lint(&IMAGE[..9], None);
+		assert_eq!((lints[0].severity, lints[0].offset), (Severity::Error, Some(9)));
+
+		let words = ProgramBuilder::new()
+			.local_register(0x07, 0x00)
+			.config_register(0, 0x02, 0x00)
+			.config_register(0, 0x06, 0x00)
+			.build()
+			.unwrap();
+		let lints: Vec<String> = lint(&words, None).iter().map(|lint| lint.to_string()).collect();
+		assert_eq!(lints, vec![
+			"error @01: LCR[0x07]: reserved local configuration register",
+			"error @04: F0 cfg[0x06]: read-only configuration register",
+			"error: function 0 would be disabled (device id 9500)",
+		]);
+
+		// the builder can't produce duplicates
+		let lints = lint(&[0x9504, 0x8400, 0x0401], None);
+		assert_eq!(lints[0].to_string(), "warning @02: LCR[0x04] already written @01");
+		// ... also in separate blocks of a function
+		let lints: Vec<String> = lint(&[0x9501, 0x8000, 0x2c15, 0x8001, 0x2c15, 0x8000, 0x2c14, 0x0000], None).iter().map(|lint| lint.to_string()).collect();
+		assert_eq!(lints, vec!["warning @06: F0 cfg[0x2c] already written @02"]);
+	}
+}
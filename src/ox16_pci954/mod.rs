@@ -1,26 +1,69 @@
+mod builder;
 mod decode;
+mod disassemble;
 mod eeprom;
+mod lint;
+mod program;
+mod text;
+
+pub use self::builder::ProgramBuilder;
 
 pub use self::decode::{
 	LocalConfiguration,
+	decode_bytes,
 	decode_resource3,
 	local_configuration_types,
 };
 
-pub use self::eeprom::open_eeprom;
+pub use self::disassemble::{
+	DisassembledWord,
+	config_register_name,
+	disassemble,
+	local_register_name,
+};
+
+pub use self::eeprom::{
+	open_eeprom,
+	open_eeprom_pins,
+	open_eeprom_with_delay,
+	open_eeprom_with_geometry,
+	open_eeprom_with_organization,
+};
+
+pub use self::lint::{
+	Lint,
+	Severity,
+	lint,
+};
 
-use crate::pci::PciEndpoint;
-use crate::serial::HardwareOperations;
+pub use self::program::{
+	FunctionConfig,
+	Header,
+	Program,
+	ProgramError,
+	RegisterWrite,
+};
+
+pub use self::text::{
+	compile,
+	decompile,
+};
+
+use crate::pci::PciDeviceInfo;
+use crate::serial::{
+	HardwareOperations,
+	Organization,
+};
 
-pub fn is_ox16_pci954(ep: PciEndpoint) -> crate::AResult<bool> {
-	match (ep.vendor()?.0, ep.device()?.0) {
-		(0x1415, 0x9500) => Ok(true), // function 0: disabled
-		(0x1415, 0x9501) => Ok(true), // function 0: Uart
-		(0x1415, 0x9510) => Ok(true), // function 1: disabled
-		(0x1415, 0x9511) => Ok(true), // function 1: 8-bit bus
-		(0x1415, 0x9512) => Ok(true), // function 1: 32-bit bus
-		(0x1415, 0x9513) => Ok(true), // function 1: parallel port
-		_ => Ok(false),
+pub fn is_ox16_pci954(info: &PciDeviceInfo) -> bool {
+	match (info.vendor().0, info.device().0) {
+		(0x1415, 0x9500) => true, // function 0: disabled
+		(0x1415, 0x9501) => true, // function 0: Uart
+		(0x1415, 0x9510) => true, // function 1: disabled
+		(0x1415, 0x9511) => true, // function 1: 8-bit bus
+		(0x1415, 0x9512) => true, // function 1: 32-bit bus
+		(0x1415, 0x9513) => true, // function 1: parallel port
+		_ => false,
 	}
 }
 
@@ -53,8 +96,13 @@ pub fn flash_program<H>(hardware: &mut H, program: &[u16]) -> crate::AResult<()>
 where
 	H: HardwareOperations,
 {
+	// the OX16PCI954 reads 16-bit words
+	ensure!(hardware.geometry().organization() == Organization::X16, "EEPROM not in x16 organization");
+	ensure!(program.len() <= hardware.geometry().words(), "{} words don't fit into EEPROM ({})", program.len(), hardware.geometry());
 	{
 		let mut hw_prog = hardware.start_programming()?;
+		// the whole program is one unit: don't stop after erasing
+		crate::signal::check_interrupted()?;
 		hw_prog.erase_all()?;
 		for address in 0..program.len() {
 			hw_prog.write(address, program[address])?;
@@ -70,12 +118,49 @@ where
 	Ok(())
 }
 
+/// Like `flash_program`, but only rewrite words that changed (the remaining
+/// EEPROM content is not erased); returns the number of changed words
+pub fn update_program<H>(hardware: &mut H, program: &[u16]) -> crate::AResult<usize>
+where
+	H: HardwareOperations,
+{
+	// the OX16PCI954 reads 16-bit words
+	ensure!(hardware.geometry().organization() == Organization::X16, "EEPROM not in x16 organization");
+	hardware.write_differential(program)
+}
+
 pub fn read_flash_program<H>(hardware: &mut H) -> crate::AResult<Vec<u16>>
 where
 	H: HardwareOperations,
 {
+	// the OX16PCI954 reads 16-bit words
+	ensure!(hardware.geometry().organization() == Organization::X16, "EEPROM not in x16 organization");
+	extract_program(hardware.read_all()?)
+}
+
+/// Read and parse the program (fails for an empty EEPROM)
+pub fn read_program<H>(hardware: &mut H) -> crate::AResult<Program>
+where
+	H: HardwareOperations,
+{
+	ensure!(hardware.geometry().organization() == Organization::X16, "EEPROM not in x16 organization");
+	let words: Vec<u16> = hardware.read_all()?.collect();
+	Ok(Program::parse(&words)?)
+}
+
+/// Like `read_flash_program`, but read the EEPROM `reads` times and use
+/// the majority of each bit (see `HardwareOperations::read_all_consensus`)
+pub fn read_flash_program_consensus<H>(hardware: &mut H, reads: usize) -> crate::AResult<Vec<u16>>
+where
+	H: HardwareOperations,
+{
+	ensure!(hardware.geometry().organization() == Organization::X16, "EEPROM not in x16 organization");
+	extract_program(hardware.read_all_consensus(reads)?.into_iter())
+}
+
+// program at the start of the EEPROM words (up to the end of the last zone)
+fn extract_program<I: Iterator<Item = u16>>(mut reader: I) -> crate::AResult<Vec<u16>> {
 	let mut buf = Vec::new();
-	let mut reader = hardware.read_all()?;
 	buf.push(reader.next().ok_or_else(|| format_err!("Unexpected end of flash data"))?);
 	if buf[0] == 0xffff {
 		warn!("Flash empty");
@@ -116,3 +201,79 @@ where
 
 	Ok(buf)
 }
+
+#[cfg(test)]
+mod test {
+	use super::{
+		IMAGE,
+		flash_program,
+		read_flash_program,
+		read_flash_program_consensus,
+		read_program,
+		update_program,
+	};
+	use crate::serial::{
+		HardwareOperations,
+		Microwire,
+		Part,
+		Simulator,
+	};
+
+	#[test]
+	fn flash_and_read_program() {
+		let geometry = Part::C46.geometry();
+		let mut ee = Microwire::new(Simulator::new(geometry), geometry);
+		assert!(read_flash_program(&mut ee).unwrap().is_empty());
+
+		flash_program(&mut ee, &IMAGE).unwrap();
+		assert_eq!(read_flash_program(&mut ee).unwrap(), &IMAGE[..]);
+		assert_eq!(read_flash_program_consensus(&mut ee, 3).unwrap(), &IMAGE[..]);
+		assert_eq!(read_program(&mut ee).unwrap().serialize().unwrap(), &IMAGE[..]);
+
+		let mut sim = ee.into_inner();
+		sim.check().unwrap();
+		assert!(!sim.is_write_enabled());
+		assert_eq!(&sim.memory()[..IMAGE.len()], &IMAGE[..]);
+		assert!(sim.memory()[IMAGE.len()..].iter().all(|&w| w == 0xffff));
+	}
+
+	#[test]
+	fn update_changed_words() {
+		let geometry = Part::C46.geometry();
+		let mut sim = Simulator::new(geometry);
+		let mut old = IMAGE.to_vec();
+		old[4] = 0x9e00;
+		old[5] = 0x1fff;
+		old.extend_from_slice(&[0x1234, 0x5678]);
+		sim.load(&old);
+		let mut ee = Microwire::new(sim, geometry);
+
+		assert_eq!(update_program(&mut ee, &IMAGE).unwrap(), 2);
+		assert_eq!(read_flash_program(&mut ee).unwrap(), &IMAGE[..]);
+		assert_eq!(update_program(&mut ee, &IMAGE).unwrap(), 0);
+
+		let mut sim = ee.into_inner();
+		sim.check().unwrap();
+		// words after the program are kept
+		assert_eq!(&sim.memory()[IMAGE.len()..IMAGE.len() + 2], &[0x1234, 0x5678]);
+	}
+
+	#[test]
+	fn reflash_detected_eeprom() {
+		let mut sim = Simulator::new(Part::C56.geometry());
+		let old: Vec<u16> = (0..128).map(|i| 0x1200 + i).collect();
+		sim.load(&old);
+		let mut ee = Microwire::detect(sim).unwrap();
+		assert_eq!(ee.geometry().address_bits(), 8);
+		assert_eq!(ee.geometry().words(), 128);
+		assert!(read_flash_program(&mut ee).is_err());
+
+		flash_program(&mut ee, &IMAGE).unwrap();
+		assert_eq!(read_flash_program(&mut ee).unwrap(), &IMAGE[..]);
+		// would wrap around to the start
+		assert!(update_program(&mut ee, &[0x1234; 129]).is_err());
+		assert!(flash_program(&mut ee, &[0x1234; 129]).is_err());
+		assert_eq!(read_flash_program(&mut ee).unwrap(), &IMAGE[..]);
+		ee.into_inner().check().unwrap();
+	}
+}
@@ -0,0 +1,301 @@
+// Typed OX16PCI954 EEPROM program
+//
+// - zone 0: header word; magic 0x950 in the upper 12 bits, bit 3 reserved,
+//   bits 2..0 flag the presence of zones 1, 2 and 3
+// - zone 1: local configuration register writes
+// - zone 2: identification words (15 bits each)
+// - zone 3: per-function blocks of PCI configuration space writes, ended by a
+//   word without the "more" flag (0x0000)
+//
+// All words in zones 1..3 use bit 15 as "more" flag: another word of the
+// list (or function block) follows.
+
+use std::fmt;
+
+use failure::Fail;
+
+const MAGIC: u16 = 0x9500;
+const MAGIC_MASK: u16 = 0xfff0;
+const RESERVED_FLAG: u16 = 0x0008;
+const ZONE1_FLAG: u16 = 0x0004;
+const ZONE2_FLAG: u16 = 0x0002;
+const ZONE3_FLAG: u16 = 0x0001;
+const MORE: u16 = 0x8000;
+
+/// Program doesn't follow the zone layout
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ProgramError {
+	/// word offset in the EEPROM (the end of the data for truncated programs)
+	pub offset: usize,
+	pub message: String,
+}
+
+impl fmt::Display for ProgramError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "EEPROM program word @{:02x}: {}", self.offset, self.message)
+	}
+}
+
+impl Fail for ProgramError {
+}
+
+/// Zone 0: which zones follow
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Header {
+	pub zone1: bool,
+	pub zone2: bool,
+	pub zone3: bool,
+}
+
+impl Header {
+	pub fn from_word(word: u16) -> Result<Self, String> {
+		if word & MAGIC_MASK != MAGIC {
+			return Err(format!("invalid magic 0x{:04x} (expected 0x{:04x})", word & MAGIC_MASK, MAGIC));
+		}
+		if word & RESERVED_FLAG != 0 {
+			return Err(format!("reserved header flag 0x{:04x} set", RESERVED_FLAG));
+		}
+		Ok(Header {
+			zone1: word & ZONE1_FLAG != 0,
+			zone2: word & ZONE2_FLAG != 0,
+			zone3: word & ZONE3_FLAG != 0,
+		})
+	}
+
+	pub fn to_word(self) -> u16 {
+		let mut word = MAGIC;
+		if self.zone1 { word |= ZONE1_FLAG; }
+		if self.zone2 { word |= ZONE2_FLAG; }
+		if self.zone3 { word |= ZONE3_FLAG; }
+		word
+	}
+}
+
+/// Byte written to a register: local configuration registers in zone 1, PCI
+/// configuration space in zone 3
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct RegisterWrite {
+	/// 7-bit register offset
+	pub register: u8,
+	pub value: u8,
+}
+
+impl RegisterWrite {
+	fn from_word(word: u16) -> Self {
+		RegisterWrite {
+			register: (word >> 8) as u8 & 0x7f,
+			value: word as u8,
+		}
+	}
+
+	fn to_word(self, more: bool) -> u16 {
+		let more = if more { MORE } else { 0 };
+		more | u16::from(self.register) << 8 | u16::from(self.value)
+	}
+}
+
+/// Zone 3 block configuring a PCI function
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct FunctionConfig {
+	/// 0 or 1
+	pub function: u8,
+	/// at least one write
+	pub writes: Vec<RegisterWrite>,
+}
+
+/// Parsed EEPROM program; `serialize(parse(words))` returns the words the
+/// program was parsed from
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Program {
+	/// zone 1 (present if not empty)
+	pub local_configuration: Vec<RegisterWrite>,
+	/// zone 2 (present if not empty); 15-bit words
+	pub identification: Vec<u16>,
+	/// zone 3; may be present with only the terminator
+	pub functions: Option<Vec<FunctionConfig>>,
+}
+
+// reads words from a zone, keeping track of the offset for errors
+struct Reader<'a> {
+	words: &'a [u16],
+	offset: usize,
+}
+
+impl<'a> Reader<'a> {
+	fn next(&mut self, zone: usize) -> Result<u16, ProgramError> {
+		match self.words.get(self.offset) {
+			None => Err(ProgramError {
+				offset: self.offset,
+				message: format!("unexpected end of data in zone {}", zone),
+			}),
+			Some(&word) => {
+				self.offset += 1;
+				Ok(word)
+			},
+		}
+	}
+
+	// error at the word returned by the last `next`
+	fn error(&self, message: String) -> ProgramError {
+		ProgramError {
+			offset: self.offset - 1,
+			message,
+		}
+	}
+}
+
+impl Program {
+	pub fn header(&self) -> Header {
+		Header {
+			zone1: !self.local_configuration.is_empty(),
+			zone2: !self.identification.is_empty(),
+			zone3: self.functions.is_some(),
+		}
+	}
+
+	/// Parse the program at the start of `words` (following words are
+	/// ignored)
+	pub fn parse(words: &[u16]) -> Result<Self, ProgramError> {
+		let mut reader = Reader { words, offset: 0 };
+		let header = Header::from_word(reader.next(0)?).map_err(|message| ProgramError { offset: 0, message })?;
+		let mut program = Program::default();
+
+		if header.zone1 {
+			loop {
+				let word = reader.next(1)?;
+				program.local_configuration.push(RegisterWrite::from_word(word));
+				if word & MORE == 0 { break; }
+			}
+		}
+		if header.zone2 {
+			loop {
+				let word = reader.next(2)?;
+				program.identification.push(word & !MORE);
+				if word & MORE == 0 { break; }
+			}
+		}
+		if header.zone3 {
+			let mut functions = Vec::new();
+			loop {
+				let word = reader.next(3)?;
+				if word & MORE == 0 {
+					if word != 0 {
+						return Err(reader.error(format!("zone 3 terminator 0x{:04x} (expected 0x0000)", word)));
+					}
+					break;
+				}
+				let function = word & !MORE;
+				if function > 1 {
+					return Err(reader.error(format!("invalid function {} in zone 3 block header 0x{:04x}", function, word)));
+				}
+				let mut writes = Vec::new();
+				loop {
+					let word = reader.next(3)?;
+					writes.push(RegisterWrite::from_word(word));
+					if word & MORE == 0 { break; }
+				}
+				functions.push(FunctionConfig { function: function as u8, writes });
+			}
+			program.functions = Some(functions);
+		}
+
+		Ok(program)
+	}
+
+	/// Fails for values that don't fit into their bit fields (or an empty
+	/// function block)
+	pub fn serialize(&self) -> crate::AResult<Vec<u16>> {
+		fn push_writes(words: &mut Vec<u16>, writes: &[RegisterWrite]) -> crate::AResult<()> {
+			for (index, write) in writes.iter().enumerate() {
+				ensure!(write.register < 0x80, "Register 0x{:02x} out of range (7 bits)", write.register);
+				words.push(write.to_word(index + 1 < writes.len()));
+			}
+			Ok(())
+		}
+
+		let mut words = vec![self.header().to_word()];
+		push_writes(&mut words, &self.local_configuration)?;
+		for (index, &word) in self.identification.iter().enumerate() {
+			ensure!(word & MORE == 0, "Identification word 0x{:04x} out of range (15 bits)", word);
+			let more = if index + 1 < self.identification.len() { MORE } else { 0 };
+			words.push(more | word);
+		}
+		if let Some(ref functions) = self.functions {
+			for function in functions {
+				ensure!(function.function <= 1, "Invalid function {}", function.function);
+				ensure!(!function.writes.is_empty(), "No writes for function {}", function.function);
+				words.push(MORE | u16::from(function.function));
+				push_writes(&mut words, &function.writes)?;
+			}
+			words.push(0x0000);
+		}
+		Ok(words)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{
+		FunctionConfig,
+		Program,
+		RegisterWrite,
+	};
+	use crate::ox16_pci954::IMAGE;
+
+	#[test]
+	fn parse_image() {
+		let program = Program::parse(&IMAGE).unwrap();
+		assert_eq!(program.local_configuration.len(), 5);
+		assert_eq!(program.local_configuration[3], RegisterWrite { register: 0x1e, value: 0x0f });
+		assert!(program.identification.is_empty());
+		assert_eq!(program.functions, Some(vec![FunctionConfig {
+			function: 1,
+			writes: vec![
+				RegisterWrite { register: 0x02, value: 0x00 },
+				RegisterWrite { register: 0x3d, value: 0x00 },
+			],
+		}]));
+		assert_eq!(program.serialize().unwrap(), &IMAGE[..]);
+
+		// trailing words are ignored
+		let mut words = IMAGE.to_vec();
+		words.extend_from_slice(&[0xffff; 4]);
+		assert_eq!(Program::parse(&words).unwrap(), program);
+	}
+
+	#[test]
+	fn roundtrip() {
+		let images: &[&[u16]] = &[
+			&[0x9500],
+			&[0x9501, 0x0000],
+			&[0x9502, 0x8123, 0x7fff],
+			&[0x9507, 0x0400, 0x0042, 0x8000, 0x8004, 0x0400, 0x8001, 0x3d00, 0x0000],
+		];
+		for &words in images {
+			let program = Program::parse(words).unwrap();
+			assert_eq!(program.serialize().unwrap(), words);
+			assert_eq!(Program::parse(&program.serialize().unwrap()).unwrap(), program);
+		}
+	}
+
+	#[test]
+	fn errors() {
+		let error = |words: &[u16]| {
+			let e = Program::parse(words).unwrap_err();
+			(e.offset, e.message)
+		};
+		assert_eq!(error(&[0xffff]).0, 0);
+		assert_eq!(error(&[0x950c, 0x0000]).0, 0);
+		// truncated zone 1: offset is the end of the data
+		assert_eq!(error(&[0x9504, 0x84ff]), (2, "unexpected end of data in zone 1".to_string()));
+		assert_eq!(error(&[]).0, 0);
+		assert_eq!(error(&[0x9505, 0x04ff, 0x8002, 0x0200, 0x0000]).0, 2);
+		assert_eq!(error(&[0x9501, 0x8001, 0x0200, 0x0001]).0, 3);
+
+		let program = Program {
+			local_configuration: vec![RegisterWrite { register: 0x80, value: 0 }],
+			..Program::default()
+		};
+		assert!(program.serialize().is_err());
+	}
+}
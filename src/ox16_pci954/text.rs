@@ -0,0 +1,300 @@
+// Text format for EEPROM programs
+//
+//     # comments start with '#'
+//     zone 1
+//     MIC[7:0] = 0xff               # local configuration register by name
+//     0x1e = uart0-irq uart1-irq    # ... or by address, with symbolic value
+//     zone 2
+//     0x1234                        # identification word
+//     zone 3
+//     function 1
+//     device id low = 0x00          # PCI configuration space register
+//     interrupt pin = none
+//
+// Values are numbers (hex with "0x", binary with "0b", otherwise decimal) or
+// a list of symbols; bit fields not named by a symbol are zero. Zones must
+// be given in order; zone 3 may be empty.
+
+use std::fmt::Write;
+
+use super::disassemble::{
+	config_register_name,
+	local_register_name,
+};
+use super::program::{
+	FunctionConfig,
+	Program,
+	RegisterWrite,
+};
+
+// symbol, mask, value
+type Symbol = (String, u8, u8);
+
+fn local_symbols(register: u8) -> Vec<Symbol> {
+	let mut symbols = Vec::new();
+	let mut field = |prefix: &str, names: &[&str], shift: usize| {
+		let mask = ((1u16 << names.len().trailing_zeros()) - 1) as u8;
+		for (value, name) in names.iter().enumerate() {
+			symbols.push((format!("{}{}", prefix, name), mask << shift, (value as u8) << shift));
+		}
+	};
+	match register {
+		0x00 => {
+			field("mode:", &["8bit-bus", "parallel-port", "subsystem-ids", "32bit-bus"], 0);
+			field("", &["", "uart-clock-out"], 2);
+			field("", &["lane0", "lane1", "lane2", "lane3"], 3);
+			field("power-down:", &["off", "4s", "129s", "518s"], 5);
+			field("", &["", "f1-mio2-pme"], 7);
+		},
+		0x04..=0x06 => {
+			for index in 0..4 {
+				let pin = usize::from(register - 0x04) * 4 + index;
+				field(&format!("mio{}:", pin), &["input", "inverted", "low", "high"], index * 2);
+			}
+		},
+		0x1e => {
+			for uart in 0..4 {
+				field("", &["", &format!("uart{}-irq", uart)], uart);
+			}
+			for pin in 0..4 {
+				field("", &["", &format!("mio{}-irq", pin)], pin + 4);
+			}
+		},
+		0x1f => {
+			for pin in 4..12 {
+				field("", &["", &format!("mio{}-irq", pin)], pin - 4);
+			}
+		},
+		_ => (),
+	}
+	// "" is the cleared flag
+	symbols.retain(|(name, _, _)| !name.is_empty());
+	symbols
+}
+
+fn config_symbols(register: u8) -> Vec<Symbol> {
+	match register {
+		0x3d => ["none", "inta", "intb", "intc", "intd"].iter().enumerate()
+			.map(|(value, &name)| (name.to_string(), 0xff, value as u8))
+			.collect(),
+		_ => Vec::new(),
+	}
+}
+
+// symbols describing all set bits (and multi-bit fields), None if there are
+// other bits
+fn describe(value: u8, symbols: &[Symbol]) -> Option<Vec<String>> {
+	let mut covered = 0u8;
+	let mut names = Vec::new();
+	for &(ref name, mask, symbol_value) in symbols {
+		if value & mask == symbol_value && covered & mask == 0 {
+			names.push(name.clone());
+			covered |= mask;
+		}
+	}
+	// (cleared flags have no symbol)
+	let fields: u8 = symbols.iter().fold(0, |bits, &(_, mask, _)| bits | mask);
+	if value & !fields != 0 || names.is_empty() {
+		None
+	} else {
+		Some(names)
+	}
+}
+
+fn parse_number(s: &str) -> crate::AResult<u32> {
+	Ok(if let Some(hex) = s.strip_prefix("0x") {
+		u32::from_str_radix(hex, 16)?
+	} else if let Some(bin) = s.strip_prefix("0b") {
+		u32::from_str_radix(bin, 2)?
+	} else {
+		s.parse()?
+	})
+}
+
+fn parse_value(s: &str, symbols: &[Symbol]) -> crate::AResult<u8> {
+	if s.starts_with(|c: char| c.is_ascii_digit()) {
+		let value = parse_number(s)?;
+		ensure!(value <= 0xff, "value {} doesn't fit into a byte", s);
+		return Ok(value as u8);
+	}
+	let mut value = 0u8;
+	let mut used = 0u8;
+	for name in s.split_whitespace() {
+		let &(_, mask, symbol_value) = match symbols.iter().find(|(symbol, _, _)| symbol.eq_ignore_ascii_case(name)) {
+			None => bail!("unknown symbol {:?}", name),
+			Some(symbol) => symbol,
+		};
+		ensure!(used & mask == 0, "symbol {:?} conflicts with a previous one", name);
+		used |= mask;
+		value |= symbol_value;
+	}
+	Ok(value)
+}
+
+fn parse_register<F: Fn(u8) -> Option<String>>(s: &str, name: F) -> crate::AResult<u8> {
+	let register = if s.starts_with(|c: char| c.is_ascii_digit()) {
+		parse_number(s)?
+	} else {
+		match (0..0x80).find(|&register| name(register).is_some_and(|name| name.eq_ignore_ascii_case(s))) {
+			None => bail!("unknown register {:?}", s),
+			Some(register) => u32::from(register),
+		}
+	};
+	ensure!(register < 0x80, "register {} out of range (7 bits)", s);
+	Ok(register as u8)
+}
+
+fn parse_write<F: Fn(u8) -> Option<String>, S: Fn(u8) -> Vec<Symbol>>(line: &str, name: F, symbols: S) -> crate::AResult<RegisterWrite> {
+	let (register, value) = match line.split_once('=') {
+		None => bail!("expected \"REGISTER = VALUE\", got {:?}", line),
+		Some(parts) => parts,
+	};
+	let register = parse_register(register.trim(), name)?;
+	let value = parse_value(value.trim(), &symbols(register))?;
+	Ok(RegisterWrite { register, value })
+}
+
+fn local_name(register: u8) -> Option<String> {
+	local_register_name(register).map(|(name, _)| name.to_string())
+}
+
+fn config_name(register: u8) -> Option<String> {
+	config_register_name(register).map(str::to_string)
+}
+
+/// Parse a program from the text format
+pub fn compile(text: &str) -> crate::AResult<Program> {
+	let mut program = Program::default();
+	let mut zone = 0;
+	let mut zones = Vec::new();
+	for (index, line) in text.lines().enumerate() {
+		let line = match line.find('#') {
+			None => line,
+			Some(pos) => &line[..pos],
+		}.trim();
+		if line.is_empty() {
+			continue;
+		}
+		with_context!(("line {}", index + 1), {
+			let words: Vec<&str> = line.split_whitespace().collect();
+			match words[..] {
+				["zone", number] => {
+					let number = number.parse::<usize>()?;
+					ensure!((1..=3).contains(&number), "invalid zone {}", number);
+					ensure!(number > zone, "zone {} after zone {}", number, zone);
+					zone = number;
+					zones.push(zone);
+					if zone == 3 {
+						program.functions = Some(Vec::new());
+					}
+				},
+				["function", function] if zone == 3 => {
+					let function = parse_number(function)?;
+					ensure!(function <= 1, "invalid function {}", function);
+					program.functions.as_mut().unwrap().push(FunctionConfig { function: function as u8, writes: Vec::new() });
+				},
+				_ => match zone {
+					0 => bail!("expected \"zone N\", got {:?}", line),
+					1 => program.local_configuration.push(parse_write(line, local_name, local_symbols)?),
+					2 => {
+						let word = parse_number(line)?;
+						ensure!(word < 0x8000, "identification word {} out of range (15 bits)", line);
+						program.identification.push(word as u16);
+					},
+					_ => match program.functions.as_mut().unwrap().last_mut() {
+						None => bail!("expected \"function N\", got {:?}", line),
+						Some(function) => function.writes.push(parse_write(line, config_name, config_symbols)?),
+					},
+				},
+			}
+			Ok(())
+		})?;
+	}
+	ensure!(!zones.contains(&1) || !program.local_configuration.is_empty(), "zone 1 without writes");
+	ensure!(!zones.contains(&2) || !program.identification.is_empty(), "zone 2 without words");
+	for function in program.functions.iter().flatten() {
+		ensure!(!function.writes.is_empty(), "function {} without writes", function.function);
+	}
+	Ok(program)
+}
+
+fn write_line(text: &mut String, indent: &str, register: String, value: u8, symbols: &[Symbol]) {
+	match describe(value, symbols) {
+		None => writeln!(text, "{}{} = 0x{:02x}", indent, register, value),
+		Some(names) => writeln!(text, "{}{} = 0x{:02x}  # {}", indent, register, value, names.join(" ")),
+	}.unwrap();
+}
+
+/// Text format of a program; `compile` returns the same program
+pub fn decompile(program: &Program) -> String {
+	let mut text = String::new();
+	let name = |register: u8, name: Option<String>| name.unwrap_or_else(|| format!("0x{:02x}", register));
+
+	if !program.local_configuration.is_empty() {
+		text.push_str("zone 1\n");
+		for write in &program.local_configuration {
+			write_line(&mut text, "\t", name(write.register, local_name(write.register)), write.value, &local_symbols(write.register));
+		}
+	}
+	if !program.identification.is_empty() {
+		text.push_str("zone 2\n");
+		for word in &program.identification {
+			writeln!(text, "\t0x{:04x}", word).unwrap();
+		}
+	}
+	if let Some(ref functions) = program.functions {
+		text.push_str("zone 3\n");
+		for function in functions {
+			writeln!(text, "\tfunction {}", function.function).unwrap();
+			for write in &function.writes {
+				write_line(&mut text, "\t\t", name(write.register, config_name(write.register)), write.value, &config_symbols(write.register));
+			}
+		}
+	}
+	text
+}
+
+#[cfg(test)]
+mod test {
+	use super::{
+		compile,
+		decompile,
+	};
+	use crate::ox16_pci954::{
+		IMAGE,
+		Program,
+	};
+
+	#[test]
+	fn decompile_image() {
+		let program = Program::parse(&IMAGE).unwrap();
+		let text = decompile(&program);
+		assert!(text.contains("\tMIC[7:0] = 0xff  # mio0:high mio1:high mio2:high mio3:high\n"));
+		assert!(text.contains("\t\tinterrupt pin = 0x00  # none\n"));
+		assert_eq!(compile(&text).unwrap(), program);
+	}
+
+	#[test]
+	fn compile_symbols() {
+		let text = "
+			# all outputs high
+			zone 1
+			MIC[7:0] = mio0:high mio1:high mio2:high mio3:high
+			mic[15:8] = 255
+			0x06 = 0b11111111
+			GIS[23:16] = uart0-irq uart1-irq uart2-irq uart3-irq
+			GIS[31:24] = 0
+			zone 3
+			function 1
+			device id low = 0x00
+			interrupt pin = none
+		";
+		assert_eq!(compile(text).unwrap().serialize().unwrap(), &IMAGE[..]);
+
+		let error = |text: &str| compile(text).unwrap_err().to_string();
+		assert_eq!(error("zone 1\nMIC[7:0] = mio0:high mio0:low"), "line 2: symbol \"mio0:low\" conflicts with a previous one");
+		assert_eq!(error("zone 3\ndevice id low = 0"), "line 2: expected \"function N\", got \"device id low = 0\"");
+		assert_eq!(error("zone 1\n0x80 = 0"), "line 2: register 0x80 out of range (7 bits)");
+		assert!(compile("zone 1\nzone 3\n").is_err());
+	}
+}
@@ -0,0 +1,282 @@
+use std::fmt;
+
+use super::{
+	PciConfigSpaceReadOnly,
+	PciDeviceInfo,
+	PciEndpoint,
+	PciResourceInfo,
+};
+
+const HEADER_TYPE: usize = 0x0e;
+const IO_BASE_LIMIT: usize = 0x1c; // byte 0x1c: base, byte 0x1d: limit
+const MEMORY_BASE_LIMIT: usize = 0x20; // word 0x20: base, word 0x22: limit
+const PREFETCHABLE_BASE_LIMIT: usize = 0x24; // word 0x24: base, word 0x26: limit
+const PREFETCHABLE_BASE_UPPER: usize = 0x28;
+const PREFETCHABLE_LIMIT_UPPER: usize = 0x2c;
+const IO_BASE_LIMIT_UPPER: usize = 0x30; // word 0x30: base, word 0x32: limit
+
+// number of BARs in a type 0 header; sysfs lists them first
+const DEVICE_BARS: usize = 6;
+
+/// Address range forwarded by a bridge (`end` is inclusive)
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Window {
+	pub start: u64,
+	pub end: u64,
+}
+
+impl Window {
+	// limit below base disables the window
+	fn new(start: u64, end: u64) -> Option<Self> {
+		if end < start {
+			None
+		} else {
+			Some(Window { start, end })
+		}
+	}
+
+	pub fn contains(&self, resource: &PciResourceInfo) -> bool {
+		self.start <= resource.start && resource.end <= self.end
+	}
+}
+
+impl fmt::Display for Window {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "0x{:x}-0x{:x}", self.start, self.end)
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct BridgeWindows {
+	pub io: Option<Window>,
+	pub memory: Option<Window>,
+	pub prefetchable: Option<Window>,
+}
+
+/// Decode forwarding windows of a PCI-to-PCI bridge (type 1 configuration header)
+pub fn decode_bridge_windows<S: PciConfigSpaceReadOnly + ?Sized>(space: &S) -> crate::AResult<BridgeWindows> {
+	let header_type = space.read_byte(HEADER_TYPE) & 0x7f;
+	ensure!(header_type == 0x01, "PCI {}: not a PCI-to-PCI bridge (header type 0x{:02x})", space.endpoint(), header_type);
+
+	let io_base_limit = space.read_dword(IO_BASE_LIMIT);
+	let io_base = io_base_limit as u8;
+	let io_limit = (io_base_limit >> 8) as u8;
+	let (io_base_upper, io_limit_upper) = if io_base & 0x0f == 0x01 {
+		// 32-bit I/O addressing
+		let upper = space.read_dword(IO_BASE_LIMIT_UPPER);
+		(u64::from(upper & 0xffff), u64::from(upper >> 16))
+	} else {
+		(0, 0)
+	};
+	let io = if 0 == io_base && 0 == io_limit {
+		// I/O forwarding not implemented
+		None
+	} else {
+		Window::new(
+			io_base_upper << 16 | u64::from(io_base & 0xf0) << 8,
+			io_limit_upper << 16 | u64::from(io_limit & 0xf0) << 8 | 0xfff,
+		)
+	};
+
+	let memory_base_limit = space.read_dword(MEMORY_BASE_LIMIT);
+	let memory = Window::new(
+		u64::from(memory_base_limit & 0xfff0) << 16,
+		u64::from((memory_base_limit >> 16) & 0xfff0) << 16 | 0xf_ffff,
+	);
+
+	let prefetchable_base_limit = space.read_dword(PREFETCHABLE_BASE_LIMIT);
+	let (prefetchable_base_upper, prefetchable_limit_upper) = if prefetchable_base_limit & 0x0f == 0x01 {
+		// 64-bit addressing
+		(
+			u64::from(space.read_dword(PREFETCHABLE_BASE_UPPER)),
+			u64::from(space.read_dword(PREFETCHABLE_LIMIT_UPPER)),
+		)
+	} else {
+		(0, 0)
+	};
+	let prefetchable = if 0 == prefetchable_base_limit {
+		// prefetchable forwarding not implemented
+		None
+	} else {
+		Window::new(
+			prefetchable_base_upper << 32 | u64::from(prefetchable_base_limit & 0xfff0) << 16,
+			prefetchable_limit_upper << 32 | u64::from((prefetchable_base_limit >> 16) & 0xfff0) << 16 | 0xf_ffff,
+		)
+	};
+
+	Ok(BridgeWindows {
+		io,
+		memory,
+		prefetchable,
+	})
+}
+
+/// Downstream BAR not forwarded by the bridge
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct WindowMismatch {
+	pub endpoint: PciEndpoint,
+	pub resource: PciResourceInfo,
+	pub windows: BridgeWindows,
+}
+
+impl fmt::Display for WindowMismatch {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fn window(w: Option<Window>) -> String {
+			w.map(|w| w.to_string()).unwrap_or_else(|| "disabled".into())
+		}
+
+		let r = &self.resource;
+		if r.is_io() {
+			write!(f, "I/O BAR{} 0x{:x}-0x{:x} outside bridge I/O window {}",
+				r.index, r.start, r.end, window(self.windows.io))
+		} else if r.is_prefetchable() {
+			write!(f, "prefetchable memory BAR{} 0x{:x}-0x{:x} outside bridge memory window {} and prefetchable window {}",
+				r.index, r.start, r.end, window(self.windows.memory), window(self.windows.prefetchable))
+		} else {
+			write!(f, "memory BAR{} 0x{:x}-0x{:x} outside bridge memory window {}",
+				r.index, r.start, r.end, window(self.windows.memory))
+		}
+	}
+}
+
+/// Verify all assigned BARs of the devices are inside the bridge windows
+///
+/// Compares bus addresses from the bridge with the CPU addresses the kernel
+/// reports; this assumes an identity mapping (as on x86).
+pub fn check_bridge_windows(windows: &BridgeWindows, devices: &[PciDeviceInfo]) -> Vec<WindowMismatch> {
+	let inside = |w: Option<Window>, r: &PciResourceInfo| w.map(|w| w.contains(r)).unwrap_or(false);
+
+	let mut result = Vec::new();
+	for device in devices {
+		for resource in device.resources().iter().take(DEVICE_BARS) {
+			if !resource.is_assigned() {
+				continue;
+			}
+			let forwarded = if resource.is_io() {
+				inside(windows.io, resource)
+			} else if resource.is_memory() {
+				inside(windows.memory, resource)
+					|| (resource.is_prefetchable() && inside(windows.prefetchable, resource))
+			} else {
+				continue;
+			};
+			if !forwarded {
+				result.push(WindowMismatch {
+					endpoint: device.endpoint(),
+					resource: *resource,
+					windows: *windows,
+				});
+			}
+		}
+	}
+	result
+}
+
+#[cfg(test)]
+mod test {
+	use super::{
+		decode_bridge_windows,
+		BridgeWindows,
+		Window,
+	};
+	use crate::pci::{
+		PciConfigSpaceReadOnly,
+		PciEndpoint,
+	};
+
+	// type 1 header in memory
+	struct Space(Vec<u8>);
+
+	impl Space {
+		fn bridge() -> Self {
+			let mut config = vec![0u8; 0x40];
+			config[0x0e] = 0x01;
+			Space(config)
+		}
+
+		fn set_dword(&mut self, offset: usize, value: u32) -> &mut Self {
+			self.0[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+			self
+		}
+	}
+
+	impl PciConfigSpaceReadOnly for Space {
+		fn endpoint(&self) -> PciEndpoint {
+			"00:1c.0".parse().unwrap()
+		}
+
+		fn len(&self) -> usize {
+			self.0.len()
+		}
+
+		fn read_byte(&self, offset: usize) -> u8 {
+			self.0[offset]
+		}
+
+		fn read_dword(&self, offset: usize) -> u32 {
+			let mut buf = [0u8; 4];
+			self.read_slice(offset, &mut buf);
+			u32::from_le_bytes(buf)
+		}
+
+		fn read_slice(&self, offset: usize, target: &mut [u8]) {
+			target.copy_from_slice(&self.0[offset..offset + target.len()]);
+		}
+
+		fn read_into_vec(&self) -> Vec<u8> {
+			self.0.clone()
+		}
+	}
+
+	fn window(start: u64, end: u64) -> Option<Window> {
+		Some(Window { start, end })
+	}
+
+	#[test]
+	fn decode_windows() {
+		let mut device = Space::bridge();
+		device.0[0x0e] = 0x80;
+		assert!(decode_bridge_windows(&device).is_err());
+
+		// nothing forwarded: I/O and prefetchable not implemented, memory
+		// base above limit
+		let mut space = Space::bridge();
+		space.set_dword(0x20, 0x0000_fff0);
+		assert_eq!(decode_bridge_windows(&space).unwrap(), BridgeWindows {
+			io: None,
+			memory: None,
+			prefetchable: None,
+		});
+
+		// 16-bit I/O, 32-bit prefetchable
+		let mut space = Space::bridge();
+		space
+			.set_dword(0x1c, 0x3020)
+			.set_dword(0x20, 0xfe10_fe00)
+			.set_dword(0x24, 0xd0f0_d000)
+			// ignored without the 32-bit I/O / 64-bit flags
+			.set_dword(0x28, 0x1)
+			.set_dword(0x30, 0x0001_0001);
+		assert_eq!(decode_bridge_windows(&space).unwrap(), BridgeWindows {
+			io: window(0x2000, 0x3fff),
+			memory: window(0xfe00_0000, 0xfe1f_ffff),
+			prefetchable: window(0xd000_0000, 0xd0ff_ffff),
+		});
+
+		// 32-bit I/O, 64-bit prefetchable with upper dwords; I/O base
+		// above limit
+		let mut space = Space::bridge();
+		space
+			.set_dword(0x1c, 0x3141)
+			.set_dword(0x24, 0xc1f1_c001)
+			.set_dword(0x28, 0x1)
+			.set_dword(0x2c, 0x2)
+			.set_dword(0x30, 0x0001_0001);
+		let windows = decode_bridge_windows(&space).unwrap();
+		assert_eq!(windows.io, None);
+		assert_eq!(windows.prefetchable, window(0x1_c000_0000, 0x2_c1ff_ffff));
+
+		space.set_dword(0x1c, 0x3121).set_dword(0x30, 0x0002_0001);
+		assert_eq!(decode_bridge_windows(&space).unwrap().io, window(0x1_2000, 0x2_3fff));
+	}
+}
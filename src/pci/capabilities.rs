@@ -0,0 +1,59 @@
+use std::fmt;
+
+use super::PciConfigSpaceReadOnly;
+
+// extended capabilities start after the legacy configuration space (only PCIe)
+const EXTENDED_CAPABILITIES_START: usize = 0x100;
+
+pub const EXTENDED_CAPABILITY_DEVICE_SERIAL_NUMBER: u16 = 0x0003;
+
+/// Find offset of extended capability (requires full configuration space access)
+pub fn find_extended_capability<S: PciConfigSpaceReadOnly + ?Sized>(space: &S, id: u16) -> Option<usize> {
+	let mut offset = EXTENDED_CAPABILITIES_START;
+	// each capability needs at least a dword; prevents looping forever on broken lists
+	for _ in 0..(space.len().saturating_sub(EXTENDED_CAPABILITIES_START) / 4) {
+		if offset + 4 > space.len() {
+			return None;
+		}
+		let header = space.read_dword(offset);
+		if 0 == header || !0 == header {
+			return None;
+		}
+		if header as u16 == id {
+			return Some(offset);
+		}
+		offset = (header >> 20) as usize & 0xffc;
+		if offset < EXTENDED_CAPABILITIES_START {
+			return None;
+		}
+	}
+	None
+}
+
+/// PCIe Device Serial Number (IEEE EUI-64)
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct DeviceSerialNumber(pub u64);
+
+impl fmt::Display for DeviceSerialNumber {
+	// same format as lspci: highest byte first
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let bytes = self.0.to_be_bytes();
+		for (i, b) in bytes.iter().enumerate() {
+			if i > 0 {
+				write!(f, "-")?;
+			}
+			write!(f, "{:02x}", b)?;
+		}
+		Ok(())
+	}
+}
+
+pub fn read_device_serial_number<S: PciConfigSpaceReadOnly + ?Sized>(space: &S) -> Option<DeviceSerialNumber> {
+	let offset = find_extended_capability(space, EXTENDED_CAPABILITY_DEVICE_SERIAL_NUMBER)?;
+	if offset + 12 > space.len() {
+		return None;
+	}
+	let lower = u64::from(space.read_dword(offset + 4));
+	let upper = u64::from(space.read_dword(offset + 8));
+	Some(DeviceSerialNumber(upper << 32 | lower))
+}
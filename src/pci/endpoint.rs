@@ -8,7 +8,12 @@ use std::io::{
 use std::num::ParseIntError;
 use std::str;
 
-use super::Driver;
+use super::{
+	Driver,
+	PciResourceInfo,
+	Vpd,
+	parse_vpd,
+};
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SlotFunction(pub u8);
@@ -181,6 +186,80 @@ impl PciEndpoint {
 		})
 	}
 
+	/// Legacy interrupt line (0 if none is assigned)
+	pub fn irq(&self) -> crate::AResult<u32> {
+		read_decimal_info_file::<u32>(*self, "irq", u32::from_str_radix)
+	}
+
+	/// MSI / MSI-X vectors allocated for the device (empty if MSI isn't used)
+	pub fn msi_irqs(&self) -> crate::AResult<Vec<u32>> {
+		let entries = match fs::read_dir(self.device_file("msi_irqs")) {
+			Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+			r => with_context!(("couldn't list MSI interrupts for PCI device {}", self), Ok(r?))?,
+		};
+		let mut result = Vec::new();
+		for entry in entries {
+			let name = entry?.file_name();
+			let irq = name.to_str().and_then(|n| n.parse::<u32>().ok())
+				.ok_or_else(|| format_err!("invalid MSI interrupt {:?} for PCI device {}", name, self))?;
+			result.push(irq);
+		}
+		result.sort();
+		Ok(result)
+	}
+
+	/// Resources (BARs, expansion ROM, bridge windows) as reported by the kernel
+	pub fn resources(&self) -> crate::AResult<Vec<PciResourceInfo>> {
+		let content = read_trimmed_info_file(*self, "resource")?;
+		let mut result = Vec::new();
+		for (index, line) in content.lines().enumerate() {
+			let resource = with_context!(("invalid resource line {} for PCI device {}: {:?}", index, self, line), {
+				let mut parts = line.split_whitespace();
+				let mut next_hex = || -> crate::AResult<u64> {
+					let v = parts.next().ok_or_else(|| format_err!("missing field"))?;
+					ensure!(v.starts_with("0x"), "field doesn't start with '0x': {:?}", v);
+					Ok(u64::from_str_radix(&v[2..], 16)?)
+				};
+				let start = next_hex()?;
+				let end = next_hex()?;
+				let flags = next_hex()?;
+				Ok(PciResourceInfo { index, start, end, flags })
+			})?;
+			result.push(resource);
+		}
+		Ok(result)
+	}
+
+	/// Vital Product Data; None if the device doesn't provide any
+	pub fn vpd(&self) -> crate::AResult<Option<Vpd>> {
+		let data = match fs::read(self.device_file("vpd")) {
+			Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+			r => with_context!(("couldn't read VPD for PCI device {}", self), Ok(r?))?,
+		};
+		if data.is_empty() {
+			return Ok(None);
+		}
+		with_context!(("invalid VPD for PCI device {}", self), {
+			Ok(Some(parse_vpd(&data)?))
+		})
+	}
+
+	/// Bridges between the root complex and the device, nearest first
+	pub fn upstream_bridges(&self) -> crate::AResult<Vec<PciEndpoint>> {
+		// /sys/devices/pci0000:00/0000:00:1c.0/0000:65:00.0
+		let path = with_context!(("Couldn't resolve sysfs path for PCI device {}", self),
+			Ok(fs::canonicalize(self.device_file(""))?)
+		)?;
+		let mut result = Vec::new();
+		for component in path.parent().into_iter().flat_map(|p| p.iter()) {
+			if let Some(ep) = component.to_str().and_then(|c| c.parse::<PciEndpoint>().ok()) {
+				result.push(ep);
+			}
+		}
+		result.reverse();
+		Ok(result)
+	}
+
 	pub fn driver(&self) -> crate::AResult<Option<Driver>> {
 		let link = self.device_file("driver");
 		match fs::symlink_metadata(&link) {
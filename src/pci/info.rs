@@ -0,0 +1,153 @@
+use super::{
+	Class,
+	DeviceID,
+	Driver,
+	PciBus,
+	PciEndpoint,
+	VendorId,
+};
+
+// flags from linux/ioport.h
+const IORESOURCE_IO: u64 = 0x0000_0100;
+const IORESOURCE_MEM: u64 = 0x0000_0200;
+const IORESOURCE_PREFETCH: u64 = 0x0000_2000;
+const IORESOURCE_MEM_64: u64 = 0x0010_0000;
+const IORESOURCE_DISABLED: u64 = 0x1000_0000;
+const IORESOURCE_UNSET: u64 = 0x2000_0000;
+
+/// Single line from the sysfs `resource` file of a device
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct PciResourceInfo {
+	pub index: usize,
+	pub start: u64,
+	pub end: u64, // inclusive
+	pub flags: u64,
+}
+
+impl PciResourceInfo {
+	/// resource neither used nor assigned
+	pub fn is_unused(&self) -> bool {
+		0 == self.flags && 0 == self.start && 0 == self.end
+	}
+
+	pub fn is_assigned(&self) -> bool {
+		!self.is_unused() && 0 == self.flags & (IORESOURCE_DISABLED | IORESOURCE_UNSET)
+	}
+
+	pub fn len(&self) -> u64 {
+		if self.is_unused() {
+			0
+		} else {
+			self.end.wrapping_sub(self.start).wrapping_add(1)
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		0 == self.len()
+	}
+
+	pub fn is_io(&self) -> bool {
+		0 != self.flags & IORESOURCE_IO
+	}
+
+	pub fn is_memory(&self) -> bool {
+		0 != self.flags & IORESOURCE_MEM
+	}
+
+	pub fn is_prefetchable(&self) -> bool {
+		0 != self.flags & IORESOURCE_PREFETCH
+	}
+
+	pub fn is_64bit(&self) -> bool {
+		0 != self.flags & IORESOURCE_MEM_64
+	}
+}
+
+/// Snapshot of the sysfs attributes of a PCI device
+///
+/// Loading reads all files once; the getters afterwards don't touch sysfs.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PciDeviceInfo {
+	endpoint: PciEndpoint,
+	vendor: VendorId,
+	device: DeviceID,
+	subsystem_vendor: VendorId,
+	subsystem_device: DeviceID,
+	class: Class,
+	driver: Option<Driver>,
+	enabled: bool,
+	irq: u32,
+	secondary_bus: Option<PciBus>,
+	resources: Vec<PciResourceInfo>,
+}
+
+impl PciDeviceInfo {
+	pub fn load(endpoint: PciEndpoint) -> crate::AResult<Self> {
+		let class = endpoint.class()?;
+		// only PCI-to-PCI bridges have a (meaningful) secondary bus
+		let secondary_bus = if class.class_code.0 == 0x06 && class.subclass_code.0 == 0x04 {
+			Some(endpoint.secondary_bus()?)
+		} else {
+			None
+		};
+
+		Ok(PciDeviceInfo {
+			endpoint,
+			vendor: endpoint.vendor()?,
+			device: endpoint.device()?,
+			subsystem_vendor: endpoint.subsystem_vendor()?,
+			subsystem_device: endpoint.subsystem_device()?,
+			class,
+			driver: endpoint.driver()?,
+			enabled: endpoint.is_enabled()?,
+			irq: endpoint.irq()?,
+			secondary_bus,
+			resources: endpoint.resources()?,
+		})
+	}
+
+	pub fn endpoint(&self) -> PciEndpoint {
+		self.endpoint
+	}
+
+	pub fn is_enabled(&self) -> bool {
+		self.enabled
+	}
+
+	pub fn vendor(&self) -> VendorId {
+		self.vendor
+	}
+
+	pub fn device(&self) -> DeviceID {
+		self.device
+	}
+
+	pub fn subsystem_vendor(&self) -> VendorId {
+		self.subsystem_vendor
+	}
+
+	pub fn subsystem_device(&self) -> DeviceID {
+		self.subsystem_device
+	}
+
+	pub fn class(&self) -> Class {
+		self.class
+	}
+
+	/// Only present for PCI-to-PCI bridges
+	pub fn secondary_bus(&self) -> Option<PciBus> {
+		self.secondary_bus
+	}
+
+	pub fn driver(&self) -> Option<&Driver> {
+		self.driver.as_ref()
+	}
+
+	pub fn irq(&self) -> u32 {
+		self.irq
+	}
+
+	pub fn resources(&self) -> &[PciResourceInfo] {
+		&self.resources
+	}
+}
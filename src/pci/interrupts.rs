@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::thread;
+use std::time::{
+	Duration,
+	Instant,
+};
+
+use super::PciEndpoint;
+
+/// Interrupts a device is configured to use
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct InterruptConfig {
+	pub irq: Option<u32>, // legacy INTx line
+	pub msi_irqs: Vec<u32>, // MSI / MSI-X vectors
+}
+
+impl InterruptConfig {
+	pub fn load(ep: PciEndpoint) -> crate::AResult<Self> {
+		let irq = match ep.irq()? {
+			0 => None,
+			irq => Some(irq),
+		};
+		Ok(InterruptConfig {
+			irq,
+			msi_irqs: ep.msi_irqs()?,
+		})
+	}
+
+	pub fn uses_msi(&self) -> bool {
+		!self.msi_irqs.is_empty()
+	}
+
+	/// interrupts actually delivered: MSI vectors if enabled, otherwise the legacy line
+	pub fn active_irqs(&self) -> Vec<u32> {
+		if self.uses_msi() {
+			self.msi_irqs.clone()
+		} else {
+			self.irq.into_iter().collect()
+		}
+	}
+}
+
+/// Single (numbered) line from /proc/interrupts
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct InterruptCount {
+	pub per_cpu: Vec<u64>,
+	pub description: String, // controller, trigger and action names
+}
+
+impl InterruptCount {
+	pub fn total(&self) -> u64 {
+		self.per_cpu.iter().sum()
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct InterruptCounts {
+	counts: BTreeMap<u32, InterruptCount>,
+}
+
+impl InterruptCounts {
+	pub fn get(&self, irq: u32) -> Option<&InterruptCount> {
+		self.counts.get(&irq)
+	}
+
+	pub fn total(&self, irq: u32) -> u64 {
+		self.get(irq).map(InterruptCount::total).unwrap_or(0)
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (u32, &InterruptCount)> {
+		self.counts.iter().map(|(irq, count)| (*irq, count))
+	}
+}
+
+pub fn parse_interrupt_counts(content: &str) -> crate::AResult<InterruptCounts> {
+	let mut lines = content.lines();
+	let cpus = match lines.next() {
+		None => bail!("empty interrupt list"),
+		Some(header) => header.split_whitespace().count(),
+	};
+
+	let mut counts = BTreeMap::new();
+	for line in lines {
+		let line = line.trim_start();
+		let colon = match line.find(':') {
+			None => continue,
+			Some(colon) => colon,
+		};
+		// skip architecture specific counters like "NMI" or "LOC"
+		let irq = match line[..colon].parse::<u32>() {
+			Err(_) => continue,
+			Ok(irq) => irq,
+		};
+
+		let mut rest = &line[colon+1..];
+		let mut per_cpu = Vec::with_capacity(cpus);
+		while per_cpu.len() < cpus {
+			let trimmed = rest.trim_start();
+			let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+			match trimmed[..end].parse::<u64>() {
+				Err(_) => break,
+				Ok(count) => per_cpu.push(count),
+			}
+			rest = &trimmed[end..];
+		}
+		ensure!(!per_cpu.is_empty(), "no counters for IRQ {} in interrupt list: {:?}", irq, line);
+
+		counts.insert(irq, InterruptCount {
+			per_cpu,
+			description: rest.split_whitespace().collect::<Vec<_>>().join(" "),
+		});
+	}
+
+	Ok(InterruptCounts { counts })
+}
+
+pub fn read_interrupt_counts() -> crate::AResult<InterruptCounts> {
+	with_context!("couldn't read /proc/interrupts", {
+		let mut content = String::new();
+		fs::File::open("/proc/interrupts")?.read_to_string(&mut content)?;
+		parse_interrupt_counts(&content)
+	})
+}
+
+/// Interrupt counts at the start and the end of an interval
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct InterruptSample {
+	pub before: InterruptCounts,
+	pub after: InterruptCounts,
+	pub elapsed: Duration,
+}
+
+impl InterruptSample {
+	pub fn delta(&self, irq: u32) -> u64 {
+		self.after.total(irq).saturating_sub(self.before.total(irq))
+	}
+
+	/// interrupts per second
+	pub fn rate(&self, irq: u32) -> f64 {
+		let secs = self.elapsed.as_secs() as f64 + f64::from(self.elapsed.subsec_nanos()) * 1e-9;
+		if secs <= 0.0 {
+			return 0.0;
+		}
+		self.delta(irq) as f64 / secs
+	}
+}
+
+pub fn sample_interrupts(interval: Duration) -> crate::AResult<InterruptSample> {
+	let start = Instant::now();
+	let before = read_interrupt_counts()?;
+	thread::sleep(interval);
+	let after = read_interrupt_counts()?;
+	Ok(InterruptSample {
+		before,
+		after,
+		elapsed: start.elapsed(),
+	})
+}
+
+#[cfg(test)]
+mod test {
+	use super::parse_interrupt_counts;
+
+	#[test]
+	fn parse_proc_interrupts() {
+		let content = "\
+           CPU0       CPU1
+  0:         45          0   IO-APIC   2-edge      timer
+ 16:       1200         34   IO-APIC  16-fasteoi   ehci_hcd:usb1, serial
+ 28:          0          7  PCI-MSI 524288-edge      eth0
+NMI:          0          0   Non-maskable interrupts
+ERR:          0
+";
+		let counts = parse_interrupt_counts(content).unwrap();
+		assert_eq!(counts.iter().count(), 3);
+		assert_eq!(counts.total(16), 1234);
+		assert_eq!(counts.get(16).unwrap().description, "IO-APIC 16-fasteoi ehci_hcd:usb1, serial");
+		assert_eq!(counts.get(28).unwrap().per_cpu, vec![0, 7]);
+		assert_eq!(counts.total(99), 0);
+	}
+}
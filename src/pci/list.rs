@@ -1,7 +1,10 @@
 use std::fs;
 use std::io;
 
-use super::PciEndpoint;
+use super::{
+	PciDeviceInfo,
+	PciEndpoint,
+};
 
 pub fn list_all_endpoints() -> io::Result<Vec<PciEndpoint>> {
 	let mut list = Vec::new();
@@ -18,3 +21,19 @@ pub fn list_all_endpoints() -> io::Result<Vec<PciEndpoint>> {
 
 	Ok(list)
 }
+
+/// Snapshot of all PCI devices, sorted by endpoint
+///
+/// Devices that can't be loaded (e.g. removed while listing) are skipped.
+pub fn list_all_devices() -> crate::AResult<Vec<PciDeviceInfo>> {
+	let mut all = list_all_endpoints()?;
+	all.sort();
+	let mut devices = Vec::new();
+	for ep in all {
+		match PciDeviceInfo::load(ep) {
+			Ok(device) => devices.push(device),
+			Err(e) => warn!("Skipping PCI device {}: {}", ep, e),
+		}
+	}
+	Ok(devices)
+}
@@ -1,9 +1,30 @@
+mod bridge;
+mod capabilities;
 mod config_space;
 mod driver;
 mod endpoint;
+mod info;
+mod interrupts;
 mod list;
 mod linux;
 mod resource;
+mod slot;
+mod vpd;
+
+pub use self::bridge::{
+	BridgeWindows,
+	Window,
+	WindowMismatch,
+	check_bridge_windows,
+	decode_bridge_windows,
+};
+
+pub use self::capabilities::{
+	DeviceSerialNumber,
+	EXTENDED_CAPABILITY_DEVICE_SERIAL_NUMBER,
+	find_extended_capability,
+	read_device_serial_number,
+};
 
 pub use self::config_space::{
 	PciConfigSpace,
@@ -15,11 +36,35 @@ pub use self::driver::{
 };
 
 pub use self::endpoint::{
-	SlotFunction,
+	Class,
+	ClassCode,
+	DeviceID,
+	PciBus,
 	PciEndpoint,
+	ProgrammingInterface,
+	ScopedEnable,
+	SlotFunction,
+	SubClassCode,
+	VendorId,
+};
+
+pub use self::info::{
+	PciDeviceInfo,
+	PciResourceInfo,
+};
+
+pub use self::interrupts::{
+	InterruptConfig,
+	InterruptCount,
+	InterruptCounts,
+	InterruptSample,
+	parse_interrupt_counts,
+	read_interrupt_counts,
+	sample_interrupts,
 };
 
 pub use self::list::{
+	list_all_devices,
 	list_all_endpoints,
 };
 
@@ -28,6 +73,19 @@ pub use self::resource::{
 	PciResourceReadOnly,
 };
 
+pub use self::slot::{
+	PhysicalSlot,
+	find_physical_slot,
+	find_physical_slot_by_name,
+	list_physical_slots,
+};
+
+pub use self::vpd::{
+	Vpd,
+	VpdField,
+	parse_vpd,
+};
+
 // OS-specific. for now linux only.
 pub use self::linux::{
 	open_config_space_readonly,
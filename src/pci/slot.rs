@@ -0,0 +1,120 @@
+use std::fmt;
+use std::fs;
+use std::io::{
+	self,
+	Read,
+};
+
+use super::{
+	PciBus,
+	PciEndpoint,
+};
+
+const SLOTS_PATH: &str = "/sys/bus/pci/slots";
+
+/// Physical (chassis) slot as registered by hotplug drivers or ACPI
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct PhysicalSlot {
+	name: String,
+	bus: PciBus,
+	slot: Option<u8>, // some drivers only register the bus
+}
+
+impl PhysicalSlot {
+	/// slot label (directory name in /sys/bus/pci/slots)
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn bus(&self) -> PciBus {
+		self.bus
+	}
+
+	pub fn slot(&self) -> Option<u8> {
+		self.slot
+	}
+
+	/// whether the endpoint is the (any function of the) device registered for the slot
+	pub fn contains(&self, ep: PciEndpoint) -> bool {
+		self.bus == ep.bus && match self.slot {
+			None => true,
+			Some(slot) => slot == ep.slot_function.slot(),
+		}
+	}
+}
+
+impl fmt::Display for PhysicalSlot {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.name)
+	}
+}
+
+// address format: "dddd:bb:ss" or "dddd:bb"
+fn parse_slot_address(name: &str, address: &str) -> crate::AResult<(PciBus, Option<u8>)> {
+	with_context!(("invalid address {:?} for PCI slot {:?}", address, name), {
+		let mut parts = address.split(':');
+		let domain = u16::from_str_radix(parts.next().unwrap_or(""), 16)?;
+		let bus = u8::from_str_radix(parts.next().ok_or_else(|| format_err!("missing bus"))?, 16)?;
+		let slot = match parts.next() {
+			None => None,
+			Some(s) => Some(u8::from_str_radix(s, 16)?),
+		};
+		ensure!(parts.next().is_none(), "too many ':'");
+		Ok((PciBus { domain, bus }, slot))
+	})
+}
+
+pub fn list_physical_slots() -> crate::AResult<Vec<PhysicalSlot>> {
+	let entries = match fs::read_dir(SLOTS_PATH) {
+		Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+		r => r?,
+	};
+
+	let mut list = Vec::new();
+	for entry in entries {
+		let entry = entry?;
+		let name = match entry.file_name().into_string() {
+			Ok(name) => name,
+			Err(name) => bail!("Invalid (Non-UTF8) PCI slot name {:?}", name),
+		};
+
+		let mut address = String::new();
+		match fs::File::open(entry.path().join("address")) {
+			// slots without address can't be mapped to devices
+			Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+			r => r?.read_to_string(&mut address)?,
+		};
+		let address = address.trim();
+		if address.is_empty() {
+			continue;
+		}
+
+		let (bus, slot) = parse_slot_address(&name, address)?;
+		list.push(PhysicalSlot { name, bus, slot });
+	}
+	list.sort();
+
+	Ok(list)
+}
+
+/// Find the physical slot a device is plugged into
+///
+/// Checks the device itself first and then the bridges upstream of it, so
+/// slots registered for the root port are found too.
+pub fn find_physical_slot(ep: PciEndpoint, slots: &[PhysicalSlot]) -> crate::AResult<Option<&PhysicalSlot>> {
+	if slots.is_empty() {
+		return Ok(None);
+	}
+	let mut candidates = vec![ep];
+	candidates.extend(ep.upstream_bridges()?);
+	for candidate in candidates {
+		if let Some(slot) = slots.iter().find(|slot| slot.contains(candidate)) {
+			return Ok(Some(slot));
+		}
+	}
+	Ok(None)
+}
+
+pub fn find_physical_slot_by_name<'a>(name: &str, slots: &'a [PhysicalSlot]) -> Option<&'a PhysicalSlot> {
+	slots.iter().find(|slot| slot.name == name)
+}
@@ -0,0 +1,151 @@
+// Parser for PCI Vital Product Data (as exposed in sysfs `vpd`)
+//
+// VPD is a list of resources:
+// - small resource tag: 0b0nnn_nlll (name n, length l)
+// - large resource tag: 0b1nnn_nnnn, followed by little-endian 16-bit length
+//
+// Resources:
+// - large 0x02: identifier string (product name)
+// - large 0x10: VPD-R, read-only fields
+// - large 0x11: VPD-W, read-write fields
+// - small 0x0f: end tag
+//
+// Fields in VPD-R/VPD-W: 2-byte keyword, 1-byte length, data
+
+use std::fmt;
+
+const SMALL_END: u8 = 0x0f;
+const LARGE_IDENTIFIER_STRING: u8 = 0x02;
+const LARGE_READ_ONLY: u8 = 0x10;
+const LARGE_READ_WRITE: u8 = 0x11;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct VpdField {
+	pub keyword: [u8; 2],
+	pub data: Vec<u8>,
+}
+
+impl VpdField {
+	pub fn keyword(&self) -> String {
+		String::from_utf8_lossy(&self.keyword).into_owned()
+	}
+
+	/// data as text, without trailing padding
+	pub fn data_string(&self) -> String {
+		String::from_utf8_lossy(&self.data).trim_end_matches(&['\0', ' '][..]).into()
+	}
+}
+
+impl fmt::Display for VpdField {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.keyword().as_str() {
+			// checksum / reserved: binary data
+			"RV" | "RW" => write!(f, "{}: <{} bytes>", self.keyword(), self.data.len()),
+			keyword => write!(f, "{}: {:?}", keyword, self.data_string()),
+		}
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Vpd {
+	pub identifier: Option<String>,
+	pub read_only: Vec<VpdField>,
+	pub read_write: Vec<VpdField>,
+	pub checksum_valid: Option<bool>, // None if there is no "RV" field
+}
+
+impl Vpd {
+	/// find read-only field by keyword (e.g. "SN" for the serial number)
+	pub fn read_only_field(&self, keyword: &str) -> Option<&VpdField> {
+		self.read_only.iter().find(|field| &field.keyword[..] == keyword.as_bytes())
+	}
+}
+
+fn parse_fields(data: &[u8], resource_offset: usize) -> crate::AResult<Vec<VpdField>> {
+	let mut fields = Vec::new();
+	let mut pos = 0;
+	while pos < data.len() {
+		ensure!(pos + 3 <= data.len(), "VPD field header at 0x{:x} truncated", resource_offset + pos);
+		let keyword = [data[pos], data[pos+1]];
+		let len = data[pos+2] as usize;
+		ensure!(pos + 3 + len <= data.len(), "VPD field {:?} at 0x{:x} truncated", String::from_utf8_lossy(&keyword), resource_offset + pos);
+		fields.push(VpdField {
+			keyword,
+			data: data[pos+3..pos+3+len].to_vec(),
+		});
+		pos += 3 + len;
+	}
+	Ok(fields)
+}
+
+pub fn parse_vpd(data: &[u8]) -> crate::AResult<Vpd> {
+	let mut vpd = Vpd::default();
+	let mut pos = 0;
+	loop {
+		ensure!(pos < data.len(), "VPD without end tag");
+		let tag = data[pos];
+		let (name, header_len, len) = if 0 != tag & 0x80 {
+			ensure!(pos + 3 <= data.len(), "VPD large resource header at 0x{:x} truncated", pos);
+			(tag & 0x7f, 3, data[pos+1] as usize | (data[pos+2] as usize) << 8)
+		} else {
+			((tag >> 3) & 0x0f, 1, (tag & 0x07) as usize)
+		};
+		let start = pos + header_len;
+		let end = start + len;
+		ensure!(end <= data.len(), "VPD resource 0x{:02x} at 0x{:x} truncated", tag, pos);
+		let body = &data[start..end];
+
+		match (0 != tag & 0x80, name) {
+			(false, SMALL_END) => break,
+			(true, LARGE_IDENTIFIER_STRING) => {
+				vpd.identifier = Some(String::from_utf8_lossy(body).trim_end().into());
+			},
+			(true, LARGE_READ_ONLY) => {
+				let fields = parse_fields(body, start)?;
+				// checksum: all bytes from the start up to and including the first RV data byte sum to zero
+				let mut field_pos = start;
+				for field in &fields {
+					if &field.keyword == b"RV" && !field.data.is_empty() {
+						let sum = data[..field_pos + 4].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+						vpd.checksum_valid = Some(0 == sum);
+						break;
+					}
+					field_pos += 3 + field.data.len();
+				}
+				vpd.read_only.extend(fields);
+			},
+			(true, LARGE_READ_WRITE) => {
+				vpd.read_write.extend(parse_fields(body, start)?);
+			},
+			_ => bail!("unknown VPD resource tag 0x{:02x} at 0x{:x}", tag, pos),
+		}
+		pos = end;
+	}
+	Ok(vpd)
+}
+
+#[cfg(test)]
+mod test {
+	use super::parse_vpd;
+
+	#[test]
+	fn parse_vpd_resources() {
+		let mut data = Vec::new();
+		data.extend_from_slice(b"\x82\x08\x00Test NIC");
+		data.extend_from_slice(b"\x90\x0f\x00PN\x03123SN\x02ABRV\x01");
+		let sum = data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+		data.push(0u8.wrapping_sub(sum));
+		data.extend_from_slice(b"\x91\x05\x00YA\x02xy");
+		data.push(0x78);
+
+		let vpd = parse_vpd(&data).unwrap();
+		assert_eq!(vpd.identifier.as_ref().map(String::as_str), Some("Test NIC"));
+		assert_eq!(vpd.read_only.len(), 3);
+		assert_eq!(vpd.read_only_field("SN").unwrap().data_string(), "AB");
+		assert_eq!(vpd.checksum_valid, Some(true));
+		assert_eq!(vpd.read_write.len(), 1);
+		assert_eq!(vpd.read_write[0].keyword(), "YA");
+
+		assert!(parse_vpd(&data[..data.len() - 1]).is_err());
+	}
+}
@@ -0,0 +1,313 @@
+// Reconstruct Microwire instructions from recorded pin samples
+//
+// Each CS-high period is a frame. DI is sampled on rising CLK; DO is taken
+// from the latest sample before the next rising CLK edge (or CS dropping),
+// as the chip shifts out a new bit on each rising edge.
+
+use std::fmt;
+use std::time::Duration;
+
+use super::{
+	Geometry,
+	Sample,
+};
+
+/// Decoded instruction
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Instruction {
+	/// sequential read: words starting at `address`
+	Read { address: usize, data: Vec<u16> },
+	Write { address: usize, data: u16 },
+	Erase { address: usize },
+	EraseAll,
+	WriteAll { data: u16 },
+	EraseWriteEnable,
+	EraseWriteDisable,
+	/// CS without start bit, reading READY/BUSY
+	StatusPoll { ready: bool },
+}
+
+impl fmt::Display for Instruction {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Instruction::Read { address, ref data } => {
+				write!(f, "READ @{:02x}:", address)?;
+				for word in data {
+					write!(f, " {:04x}", word)?;
+				}
+				Ok(())
+			},
+			Instruction::Write { address, data } => write!(f, "WRITE @{:02x} {:04x}", address, data),
+			Instruction::Erase { address } => write!(f, "ERASE @{:02x}", address),
+			Instruction::EraseAll => write!(f, "ERAL"),
+			Instruction::WriteAll { data } => write!(f, "WRAL {:04x}", data),
+			Instruction::EraseWriteEnable => write!(f, "EWEN"),
+			Instruction::EraseWriteDisable => write!(f, "EWDS"),
+			Instruction::StatusPoll { ready: true } => write!(f, "status: READY"),
+			Instruction::StatusPoll { ready: false } => write!(f, "status: BUSY"),
+		}
+	}
+}
+
+/// Decoded CS-high period
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Frame {
+	pub start: Duration,
+	/// None if the trace ended with CS high
+	pub end: Option<Duration>,
+	pub instruction: Option<Instruction>,
+	pub errors: Vec<String>,
+}
+
+impl fmt::Display for Frame {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:>12}ns: ", self.start.as_nanos())?;
+		match self.instruction {
+			Some(ref instruction) => write!(f, "{}", instruction)?,
+			None => write!(f, "-")?,
+		}
+		for error in &self.errors {
+			write!(f, " [{}]", error)?;
+		}
+		Ok(())
+	}
+}
+
+struct FrameDecoder {
+	geometry: Geometry,
+	start: Duration,
+	started: bool,
+	leading_cycles: usize,
+	status_sampled: bool,
+	// DI bits after the start bit
+	bits: Vec<bool>,
+	// DO bits after the READ address (dummy zero first)
+	output: Vec<Option<bool>>,
+	data_out: Option<bool>,
+}
+
+impl FrameDecoder {
+	fn new(geometry: Geometry, sample: &Sample) -> Self {
+		FrameDecoder {
+			geometry,
+			start: sample.time,
+			started: false,
+			leading_cycles: 0,
+			status_sampled: false,
+			bits: Vec::new(),
+			output: Vec::new(),
+			data_out: sample.data_out,
+		}
+	}
+
+	fn header_bits(&self) -> usize {
+		2 + self.geometry.address_bits()
+	}
+
+	fn is_read(&self) -> bool {
+		self.bits.len() >= self.header_bits() && self.bits[0] && !self.bits[1]
+	}
+
+	fn data_out(&mut self, data_out: Option<bool>) {
+		if data_out != self.data_out && !self.started {
+			// READY/BUSY status changed
+			self.status_sampled = true;
+		}
+		self.data_out = data_out;
+	}
+
+	fn clock_rising(&mut self, data_in: bool) {
+		if !self.started {
+			if data_in {
+				self.started = true;
+			} else {
+				self.leading_cycles += 1;
+			}
+		} else if self.is_read() {
+			self.output.push(self.data_out);
+		} else {
+			self.bits.push(data_in);
+		}
+	}
+
+	fn finish(mut self, end: Option<Duration>) -> Frame {
+		let mut errors = Vec::new();
+		let instruction = self.decode(&mut errors);
+		Frame {
+			start: self.start,
+			end,
+			instruction,
+			errors,
+		}
+	}
+
+	fn decode(&mut self, errors: &mut Vec<String>) -> Option<Instruction> {
+		if !self.started {
+			if 0 == self.leading_cycles && !self.status_sampled {
+				return None;
+			}
+			match self.data_out {
+				Some(ready) => return Some(Instruction::StatusPoll { ready }),
+				None => {
+					errors.push(format!("no start bit ({} clock cycles)", self.leading_cycles));
+					return None;
+				},
+			}
+		}
+		// a frame one bit short probably used the first instruction bit as start bit
+		let hint = |missing: usize| if 1 == missing { " (missing start bit?)" } else { "" };
+		let header_bits = self.header_bits();
+		if self.bits.len() < header_bits {
+			errors.push(format!("incomplete instruction ({} of {} bits after start bit){}",
+				self.bits.len(), header_bits, hint(header_bits - self.bits.len())));
+			return None;
+		}
+		let address = self.bits[2..header_bits].iter().fold(0usize, |a, &b| a << 1 | b as usize);
+		let data = &self.bits[header_bits..];
+		let word_bits = self.geometry.word_bits();
+		let to_word = |bits: &[bool]| bits.iter().fold(0u16, |w, &b| w << 1 | b as u16);
+
+		let opcode = (self.bits[0], self.bits[1]);
+		let extended = address >> (self.geometry.address_bits() - 2);
+		let with_data = match opcode {
+			(true, false) => return Some(self.decode_read(address, errors)),
+			(false, true) => true,
+			(false, false) => 0b01 == extended,
+			(true, true) => false,
+		};
+		if with_data && data.len() != word_bits {
+			if data.len() < word_bits {
+				errors.push(format!("incomplete data ({} of {} bits){}", data.len(), word_bits, hint(word_bits - data.len())));
+				return None;
+			}
+			errors.push(format!("{} extra clock cycles after data", data.len() - word_bits));
+		} else if !with_data && !data.is_empty() {
+			errors.push(format!("{} extra clock cycles after address", data.len()));
+		}
+		Some(match opcode {
+			(false, true) => Instruction::Write { address, data: to_word(&data[..word_bits]) },
+			(true, true) => Instruction::Erase { address },
+			_ => match extended {
+				0b00 => Instruction::EraseWriteDisable,
+				0b01 => Instruction::WriteAll { data: to_word(&data[..word_bits]) },
+				0b10 => Instruction::EraseAll,
+				_ => Instruction::EraseWriteEnable,
+			},
+		})
+	}
+
+	fn decode_read(&mut self, address: usize, errors: &mut Vec<String>) -> Instruction {
+		// bit shifted out on the last rising edge, read after it
+		self.output.push(self.data_out);
+		let word_bits = self.geometry.word_bits();
+
+		match self.output[0] {
+			Some(false) => (),
+			Some(true) => errors.push("missing dummy zero bit".into()),
+			None => errors.push("dummy zero bit not sampled".into()),
+		}
+		let bits = &self.output[1..];
+		if bits.iter().any(Option::is_none) {
+			errors.push("DO not sampled".into());
+		}
+		let remaining = bits.len() % word_bits;
+		if remaining != 0 {
+			errors.push(format!("incomplete data word ({} of {} bits)", remaining, word_bits));
+		}
+		let data = bits.chunks(word_bits).filter(|chunk| chunk.len() == word_bits).map(|chunk| {
+			chunk.iter().fold(0u16, |w, &b| w << 1 | (b == Some(true)) as u16)
+		}).collect();
+		Instruction::Read { address, data }
+	}
+}
+
+/// Decode all frames in the samples
+pub fn decode(samples: &[Sample], geometry: Geometry) -> Vec<Frame> {
+	let mut frames = Vec::new();
+	let mut frame: Option<FrameDecoder> = None;
+	let mut previous: Option<&Sample> = None;
+
+	for sample in samples {
+		let (was_selected, had_clock) = previous.map(|p| (p.pins.chip_select, p.pins.clock)).unwrap_or((false, false));
+		if was_selected && !sample.pins.chip_select {
+			if let Some(mut f) = frame.take() {
+				// DO is still driven when CS drops
+				f.data_out(sample.data_out);
+				frames.push(f.finish(Some(sample.time)));
+			}
+		} else if !was_selected && sample.pins.chip_select {
+			frame = Some(FrameDecoder::new(geometry, sample));
+		} else if let Some(ref mut f) = frame {
+			// DO in the sample of a rising edge is still the previous bit: the
+			// chip changes it only after the edge
+			f.data_out(sample.data_out);
+			if !had_clock && sample.pins.clock {
+				f.clock_rising(sample.pins.data);
+			}
+		}
+		previous = Some(sample);
+	}
+	if let Some(f) = frame {
+		frames.push(f.finish(None));
+	}
+	frames
+}
+
+#[cfg(test)]
+mod test {
+	use super::{
+		Instruction,
+		decode,
+	};
+	use crate::serial::{
+		HardwareOperations,
+		Microwire,
+		Part,
+		Simulator,
+		Trace,
+	};
+
+	#[test]
+	fn decode_simulated_trace() {
+		let geometry = Part::C46.geometry();
+		let mut ee = Microwire::new(Trace::new(Simulator::new(geometry)), geometry);
+		{
+			let mut prog = ee.start_programming().unwrap();
+			prog.write(0x05, 0xbeef).unwrap();
+		}
+		let words: Vec<u16> = ee.read_all().unwrap().skip(4).take(2).collect();
+		assert_eq!(words, vec![0xffff, 0xbeef]);
+
+		let trace = ee.into_inner();
+		let frames = decode(trace.samples(), geometry);
+		assert!(frames.iter().all(|f| f.errors.is_empty()), "{:?}", frames);
+		let instructions: Vec<Instruction> = frames.into_iter()
+			.filter_map(|f| f.instruction)
+			.filter(|i| match i { Instruction::StatusPoll { .. } => false, _ => true })
+			.collect();
+		assert_eq!(instructions, vec![
+			Instruction::EraseWriteEnable,
+			Instruction::Write { address: 0x05, data: 0xbeef },
+			Instruction::EraseWriteDisable,
+			Instruction::Read { address: 0, data: vec![0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xbeef] },
+		]);
+	}
+
+	#[test]
+	fn decode_missing_dummy_zero() {
+		// READ @00 on a 6-bit address EEPROM: the chip never pulls DO low
+		let geometry = Part::C46.geometry();
+		let mut sim = Simulator::new(geometry);
+		sim.load(&[0x0000]);
+		let mut ee = Microwire::new(Trace::new(sim), geometry);
+		assert!(ee.read(0).is_ok());
+
+		let mut samples = ee.into_inner().samples().to_vec();
+		for sample in &mut samples {
+			sample.data_out = Some(true);
+		}
+		let frames = decode(&samples, geometry);
+		let read = frames.iter().find(|f| match f.instruction { Some(Instruction::Read { .. }) => true, _ => false }).unwrap();
+		assert_eq!(read.errors, vec!["missing dummy zero bit".to_string()]);
+	}
+}
@@ -0,0 +1,237 @@
+// Microwire EEPROM simulated from a dump file
+//
+// Dumps are either text as printed by `axxon-debug dump_eeprom` ("@00: 9505"
+// per line) or binary; binary x16 dumps store each word big-endian, the
+// order the bits are shifted out.
+
+use std::fs;
+use std::path::{
+	Path,
+	PathBuf,
+};
+use std::time::Instant;
+
+use super::{
+	Geometry,
+	Hardware,
+	Operation,
+	Organization,
+	OutPins,
+	Part,
+	Simulator,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DumpFormat {
+	Text,
+	Binary,
+}
+
+impl DumpFormat {
+	/// text if the extension is ".txt"
+	pub fn from_path(path: &Path) -> Self {
+		match path.extension() {
+			Some(ext) if ext == "txt" => DumpFormat::Text,
+			_ => DumpFormat::Binary,
+		}
+	}
+}
+
+fn parse_text(text: &str) -> crate::AResult<Vec<u16>> {
+	let mut words = Vec::new();
+	for (index, line) in text.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+		with_context!(("line {}", index + 1), {
+			let (address, word) = match line.strip_prefix('@').and_then(|l| l.split_once(':')) {
+				None => bail!("expected \"@ADDRESS: WORD\", got {:?}", line),
+				Some(parts) => parts,
+			};
+			let address = usize::from_str_radix(address.trim(), 16)?;
+			ensure!(address == words.len(), "expected address {:02x}, got {:02x}", words.len(), address);
+			words.push(u16::from_str_radix(word.trim(), 16)?);
+			Ok(())
+		})?;
+	}
+	Ok(words)
+}
+
+fn parse_binary(data: &[u8], organization: Organization) -> crate::AResult<Vec<u16>> {
+	Ok(match organization {
+		Organization::X8 => data.iter().map(|&b| u16::from(b)).collect(),
+		Organization::X16 => {
+			ensure!(data.len().is_multiple_of(2), "odd number of bytes in x16 dump");
+			data.chunks(2).map(|w| u16::from(w[0]) << 8 | u16::from(w[1])).collect()
+		},
+	})
+}
+
+/// Read the words of a dump file (text or binary by extension)
+pub fn read_dump<P: AsRef<Path>>(path: P, organization: Organization) -> crate::AResult<Vec<u16>> {
+	let path = path.as_ref();
+	with_context!(("EEPROM dump {:?}", path), {
+		let data = fs::read(path)?;
+		match DumpFormat::from_path(path) {
+			DumpFormat::Text => parse_text(&String::from_utf8(data)?),
+			DumpFormat::Binary => parse_binary(&data, organization),
+		}
+	})
+}
+
+fn format_dump(words: &[u16], format: DumpFormat, organization: Organization) -> Vec<u8> {
+	match format {
+		DumpFormat::Text => {
+			let digits = match organization {
+				Organization::X8 => 2,
+				Organization::X16 => 4,
+			};
+			words.iter().enumerate()
+				.map(|(address, word)| format!("@{:02x}: {:0digits$x}\n", address, word, digits = digits))
+				.collect::<String>()
+				.into_bytes()
+		},
+		DumpFormat::Binary => match organization {
+			Organization::X8 => words.iter().map(|&w| w as u8).collect(),
+			Organization::X16 => words.iter().flat_map(|&w| vec![(w >> 8) as u8, w as u8]).collect(),
+		},
+	}
+}
+
+/// Write words to a dump file (text or binary by extension)
+pub fn write_dump<P: AsRef<Path>>(path: P, words: &[u16], organization: Organization) -> crate::AResult<()> {
+	let path = path.as_ref();
+	let data = format_dump(words, DumpFormat::from_path(path), organization);
+	fs::write(path, data).map_err(|e| format_err!("Couldn't write EEPROM dump {:?}: {}", path, e))?;
+	Ok(())
+}
+
+/// Simulated EEPROM loaded from a dump file; changes are written back by
+/// `save` (or when dropped)
+pub struct FileEeprom {
+	simulator: Simulator,
+	path: PathBuf,
+	format: DumpFormat,
+	saved: Vec<u16>,
+}
+
+impl FileEeprom {
+	/// The size of the dump selects the part (93C46 .. 93C86)
+	pub fn open<P: AsRef<Path>>(path: P, organization: Organization) -> crate::AResult<Self> {
+		let path = path.as_ref();
+		let format = DumpFormat::from_path(path);
+		let words = read_dump(path, organization)?;
+		let geometry = Part::ALL.iter()
+			.map(|part| part.geometry_with_organization(organization))
+			.find(|geometry| geometry.words() == words.len())
+			.ok_or_else(|| format_err!("EEPROM dump {:?}: no 93Cx6 EEPROM with {} words in {:?} organization", path, words.len(), organization))?;
+		let blank = (!0u32 >> (32 - geometry.word_bits())) as u16;
+		if let Some(word) = words.iter().find(|&&word| word > blank) {
+			bail!("EEPROM dump {:?}: word {:04x} too large for {:?} organization", path, word, organization);
+		}
+
+		let mut simulator = Simulator::new(geometry);
+		simulator.load(&words);
+		Ok(FileEeprom {
+			simulator,
+			path: path.to_path_buf(),
+			format,
+			saved: words,
+		})
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	pub fn geometry(&self) -> Geometry {
+		self.simulator.geometry()
+	}
+
+	pub fn simulator(&self) -> &Simulator {
+		&self.simulator
+	}
+
+	/// Write the dump file if the contents changed
+	pub fn save(&mut self) -> crate::AResult<()> {
+		let words = self.simulator.memory();
+		if words == &self.saved[..] {
+			return Ok(());
+		}
+		let data = format_dump(words, self.format, self.geometry().organization());
+		fs::write(&self.path, data).map_err(|e| format_err!("Couldn't write EEPROM dump {:?}: {}", self.path, e))?;
+		self.saved = words.to_vec();
+		Ok(())
+	}
+}
+
+impl Drop for FileEeprom {
+	fn drop(&mut self) {
+		if let Err(e) = self.save() {
+			error!("{}", e);
+		}
+	}
+}
+
+impl Hardware for FileEeprom {
+	fn set_pins(&mut self, pins: OutPins) {
+		self.simulator.set_pins(pins)
+	}
+
+	fn read_pin(&mut self) -> bool {
+		self.simulator.read_pin()
+	}
+
+	fn delay(&mut self) {
+		self.simulator.delay()
+	}
+
+	fn now(&self) -> Instant {
+		self.simulator.now()
+	}
+
+	fn begin_operation(&mut self, operation: Operation) {
+		self.simulator.begin_operation(operation)
+	}
+
+	fn busy_poll(&mut self) {
+		self.simulator.busy_poll()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::fs;
+
+	use super::FileEeprom;
+	use crate::ox16_pci954::{
+		IMAGE,
+		read_flash_program,
+		update_program,
+	};
+	use crate::serial::{
+		HardwareOperations,
+		Microwire,
+		Organization,
+	};
+
+	#[test]
+	fn update_text_dump() {
+		let path = std::env::temp_dir().join(format!("ox16pci954-{}.txt", std::process::id()));
+		let text: String = (0..64).map(|address| format!("@{:02x}: {:04x}\n", address, if address < 2 { 0x9500 } else { 0xffff })).collect();
+		fs::write(&path, text).unwrap();
+
+		let file = FileEeprom::open(&path, Organization::X16).unwrap();
+		let mut ee = Microwire::detect(file).unwrap();
+		assert_eq!(ee.geometry().words(), 64);
+		assert_eq!(read_flash_program(&mut ee).unwrap(), &[0x9500]);
+		assert_eq!(update_program(&mut ee, &IMAGE).unwrap(), IMAGE.len());
+		drop(ee);
+
+		let mut ee = Microwire::detect(FileEeprom::open(&path, Organization::X16).unwrap()).unwrap();
+		assert_eq!(read_flash_program(&mut ee).unwrap(), &IMAGE[..]);
+		assert!(fs::read_to_string(&path).unwrap().starts_with("@00: 9505\n@01: 84ff\n"));
+		fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+use std::str;
+
+/// Microwire EEPROMs of the 93Cx6 family (16-bit organization)
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Part {
+	C46, // 1 kbit
+	C56, // 2 kbit
+	C66, // 4 kbit
+	C76, // 8 kbit
+	C86, // 16 kbit
+}
+
+impl Part {
+	pub const ALL: [Part; 5] = [Part::C46, Part::C56, Part::C66, Part::C76, Part::C86];
+
+	/// geometry in 16-bit organization (ORG pin high)
+	pub fn geometry(self) -> Geometry {
+		self.geometry_with_organization(Organization::X16)
+	}
+
+	pub fn geometry_with_organization(self, organization: Organization) -> Geometry {
+		let (address_bits, words) = match self {
+			// 93C56 and 93C76 ignore the highest address bit
+			Part::C46 => (6, 64),
+			Part::C56 => (8, 128),
+			Part::C66 => (8, 256),
+			Part::C76 => (10, 512),
+			Part::C86 => (10, 1024),
+		};
+		match organization {
+			Organization::X16 => Geometry { address_bits, words, organization },
+			// one more address bit to select the byte
+			Organization::X8 => Geometry { address_bits: address_bits + 1, words: words * 2, organization },
+		}
+	}
+}
+
+impl fmt::Display for Part {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let name = match *self {
+			Part::C46 => "93C46",
+			Part::C56 => "93C56",
+			Part::C66 => "93C66",
+			Part::C76 => "93C76",
+			Part::C86 => "93C86",
+		};
+		write!(f, "{}", name)
+	}
+}
+
+impl str::FromStr for Part {
+	type Err = ::failure::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		// allow vendor prefixes/suffixes like "AT93C46" or "93C46B"
+		let upper = s.to_ascii_uppercase();
+		let pos = match upper.find("93C") {
+			None => bail!("Unknown 93Cx6 EEPROM: {:?}", s),
+			Some(pos) => pos,
+		};
+		match upper.get(pos+3..pos+5) {
+			Some("46") => Ok(Part::C46),
+			Some("56") => Ok(Part::C56),
+			Some("66") => Ok(Part::C66),
+			Some("76") => Ok(Part::C76),
+			Some("86") => Ok(Part::C86),
+			_ => bail!("Unknown 93Cx6 EEPROM: {:?}", s),
+		}
+	}
+}
+
+/// Memory organization, selected by the ORG pin
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Organization {
+	X8, // ORG low: bytes
+	X16, // ORG high (or unconnected): 16-bit words
+}
+
+impl Organization {
+	pub fn word_bits(self) -> usize {
+		match self {
+			Organization::X8 => 8,
+			Organization::X16 => 16,
+		}
+	}
+}
+
+/// Addressing of a Microwire EEPROM
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Geometry {
+	address_bits: usize,
+	words: usize, // bytes in x8 organization
+	organization: Organization,
+}
+
+impl Geometry {
+	pub fn new(address_bits: usize, words: usize, organization: Organization) -> crate::AResult<Self> {
+		// need two bits for the extended opcodes (EWEN, EWDS, ERAL, WRAL)
+		ensure!((2..=16).contains(&address_bits), "Unsupported EEPROM address width: {} bits", address_bits);
+		ensure!(words > 0 && words <= 1 << address_bits, "EEPROM size {} doesn't fit address width of {} bits", words, address_bits);
+		Ok(Geometry { address_bits, words, organization })
+	}
+
+	/// assume the full address space is used (the real size might be smaller,
+	/// see `wrapped`)
+	pub fn from_address_bits(address_bits: usize, organization: Organization) -> crate::AResult<Self> {
+		Geometry::new(address_bits, 1 << address_bits.min(16), organization)
+	}
+
+	pub fn address_bits(&self) -> usize {
+		self.address_bits
+	}
+
+	/// Smaller part with the same address width, which ignores the highest
+	/// address bit (93C56 for 93C66, 93C76 for 93C86)
+	pub fn wrapped(&self) -> Option<Geometry> {
+		Part::ALL.iter()
+			.map(|part| part.geometry_with_organization(self.organization))
+			.find(|geometry| geometry.address_bits == self.address_bits && geometry.words < self.words)
+	}
+
+	/// number of addressable words (or bytes in x8 organization)
+	pub fn words(&self) -> usize {
+		self.words
+	}
+
+	pub fn organization(&self) -> Organization {
+		self.organization
+	}
+
+	/// bits per addressable unit
+	pub fn word_bits(&self) -> usize {
+		self.organization.word_bits()
+	}
+
+	/// Address field for the extended instructions (opcode 0b00), which use
+	/// the two highest address bits; the remaining bits are "don't care".
+	pub(super) fn extended_address(&self, instruction: u16) -> u16 {
+		(instruction & 0b11) << (self.address_bits - 2)
+	}
+}
+
+impl fmt::Display for Geometry {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} x {}-bit ({} address bits)", self.words, self.word_bits(), self.address_bits)
+	}
+}
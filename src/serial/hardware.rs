@@ -4,6 +4,9 @@ use std::time::{
 	Instant,
 };
 
+use super::Operation;
+
+// see `DatasheetTiming::min_edge`
 const CLOCK_EDGE: Duration = Duration::from_nanos(250);
 // const CLOCK_FULL: Duration = Duration::from_nanos(500);
 
@@ -34,4 +37,43 @@ pub trait Hardware {
 	fn delay(&mut self) {
 		reliable_sleep(CLOCK_EDGE);
 	}
+
+	// clock for timeouts
+	fn now(&self) -> Instant {
+		Instant::now()
+	}
+
+	// hooks for `Statistics`: an instruction is about to start, and the
+	// READY/BUSY status read BUSY once more
+	fn begin_operation(&mut self, _operation: Operation) {
+	}
+
+	fn busy_poll(&mut self) {
+	}
+}
+
+impl<H: Hardware + ?Sized> Hardware for &mut H {
+	fn set_pins(&mut self, pins: OutPins) {
+		(**self).set_pins(pins)
+	}
+
+	fn read_pin(&mut self) -> bool {
+		(**self).read_pin()
+	}
+
+	fn delay(&mut self) {
+		(**self).delay()
+	}
+
+	fn now(&self) -> Instant {
+		(**self).now()
+	}
+
+	fn begin_operation(&mut self, operation: Operation) {
+		(**self).begin_operation(operation)
+	}
+
+	fn busy_poll(&mut self) {
+		(**self).busy_poll()
+	}
 }
@@ -2,10 +2,14 @@ use std::ops::{
 	Deref,
 	DerefMut,
 };
+use std::time::Duration;
 
 use super::{
 	Hardware,
+	Operation,
 	OutPins,
+	Timeout,
+	timeout::Deadline,
 };
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
@@ -99,8 +103,14 @@ impl<'a, H: ?Sized+LowLevel> ReadTransaction<'a, H> {
 
 	// read 16-bit word, starting with highest bit
 	pub fn receive_word(&mut self) -> u16 {
+		self.receive_bits(16)
+	}
+
+	// read `num` bits (at most 16), starting with highest bit
+	pub fn receive_bits(&mut self, num: usize) -> u16 {
+		assert!(num <= 16);
 		let mut result = 0u16;
-		for bit in (0..16).rev() {
+		for bit in (0..num).rev() {
 			let bit_mask = 1u16 << bit;
 			// send zero bit for each bit we want to read
 			if self.receive_bit() {
@@ -125,11 +135,31 @@ impl<'a, H: ?Sized+LowLevel> DerefMut for ReadTransaction<'a, H> {
 	}
 }
 
-pub struct ProgramTransaction<'a, H: ?Sized+LowLevel+'a>(&'a mut H);
+pub struct ProgramTransaction<'a, H: ?Sized+LowLevel+'a> {
+	hardware: &'a mut H,
+	operation: Operation,
+	timeout: Duration,
+	finished: bool,
+}
+
+impl<'a, H: ?Sized+LowLevel> ProgramTransaction<'a, H> {
+	// drop CS to start the self-timed cycle and wait for it to complete
+	pub fn finish(mut self) -> crate::AResult<()> {
+		self.finished = true;
+		let deadline = Deadline::new(self.operation, self.timeout, self.hardware.now());
+		self.hardware._wait_for_completion(&deadline)?;
+		Ok(())
+	}
+}
 
 impl<'a, H: ?Sized+LowLevel> Drop for ProgramTransaction<'a, H> {
 	fn drop(&mut self) {
-		self.0._wait_for_completion();
+		if !self.finished {
+			let deadline = Deadline::new(self.operation, self.timeout, self.hardware.now());
+			if let Err(e) = self.hardware._wait_for_completion(&deadline) {
+				error!("{}", e);
+			}
+		}
 	}
 }
 
@@ -137,13 +167,13 @@ impl<'a, H: ?Sized+LowLevel> Deref for ProgramTransaction<'a, H> {
 	type Target = H;
 
 	fn deref(&self) -> &Self::Target {
-		&self.0
+		self.hardware
 	}
 }
 
 impl<'a, H: ?Sized+LowLevel> DerefMut for ProgramTransaction<'a, H> {
 	fn deref_mut(&mut self) -> &mut Self::Target {
-		&mut self.0
+		self.hardware
 	}
 }
 
@@ -181,12 +211,13 @@ trait InternalLowLevel: Hardware {
 	}
 
 	// start instruction
-	fn _start_instruction(&mut self) {
+	fn _start_instruction(&mut self, deadline: &Deadline) -> Result<(), Timeout> {
 		// make sure chip isn't BUSY
-		self._wait_for_completion();
+		self._wait_for_completion(deadline)?;
 
 		// now trigger CLK up and down while CS + DATA is up, in short: a "1 bit"
 		self.signal(Signal::One);
+		Ok(())
 	}
 
 	// turn all pins off and wait for a half cycle
@@ -196,7 +227,7 @@ trait InternalLowLevel: Hardware {
 	}
 
 	// wait for previous write/erase instruction to finish; also clears at the end
-	fn _wait_for_completion(&mut self) {
+	fn _wait_for_completion(&mut self, deadline: &Deadline) -> Result<(), Timeout> {
 		// one cycle with low CS + DATA
 		self.signal(Signal::Clear);
 		// now send 0 bits until data input is high; data input should be pulled
@@ -213,10 +244,17 @@ trait InternalLowLevel: Hardware {
 		// Timing: "status valid" becomes ready after a full CLK cycle with CS,
 		// which we just did
 		while !self.read_pin() {
+			self.busy_poll();
+			if let Err(e) = deadline.check(self.now()) {
+				// don't leave CS asserted
+				self._finish_instruction();
+				return Err(e);
+			}
 			// technicall we could just wait and poll, but let's drive CLK too.
 			self.signal(Signal::Zero);
 		}
 		self._finish_instruction();
+		Ok(())
 	}
 }
 
@@ -264,16 +302,27 @@ pub trait LowLevel: Hardware {
 		Ok(())
 	}
 
-	fn start_transaction(&mut self) -> Transaction<Self> {
-		self._start_instruction();
+	// `timeout`: waiting for a previous erase/write cycle to complete
+	fn start_transaction(&mut self, operation: Operation, timeout: Duration) -> crate::AResult<Transaction<Self>> {
+		self.begin_operation(operation);
+		let deadline = Deadline::new(operation, timeout, self.now());
+		self._start_instruction(&deadline)?;
 
-		Transaction(self)
+		Ok(Transaction(self))
 	}
 
-	fn start_program_transaction(&mut self) -> ProgramTransaction<Self> {
-		self._start_instruction();
-
-		ProgramTransaction(self)
+	// `timeout`: waiting for a previous and for this erase/write cycle to complete
+	fn start_program_transaction(&mut self, operation: Operation, timeout: Duration) -> crate::AResult<ProgramTransaction<Self>> {
+		self.begin_operation(operation);
+		let deadline = Deadline::new(operation, timeout, self.now());
+		self._start_instruction(&deadline)?;
+
+		Ok(ProgramTransaction {
+			hardware: self,
+			operation,
+			timeout,
+			finished: false,
+		})
 	}
 }
 
@@ -1,4 +1,5 @@
-/// Protocol for Microchip 93C46B, a 1-kbit EEPROM (organized as 64 x 16bit)
+/// Protocol for Microwire EEPROMs like the Microchip 93C46B, a 1-kbit EEPROM
+/// (organized as 64 x 16bit), and the larger parts of the 93Cx6 family
 ///
 /// Sometimes called "I²C", but it really isn't. For example there are separate
 /// pins for data IN and OUT, and there is also a CHIP SELECT pin.
@@ -6,12 +7,14 @@
 /// Instructions:
 /// - Startbit: "1"
 /// - 2-bit Opcode
-/// - 6-bit Address
+/// - Address: 6 bits (93C46), 8 bits (93C56, 93C66) or 10 bits (93C76, 93C86)
 ///
 /// Some instructions have a DATA phase following (either send or recv) for 16
-/// bits, so the total request takes either 9 or 16 CLK cycles.
+/// bits, so the total request takes either 9 or 25 CLK cycles (for 6-bit
+/// addresses).
 ///
-/// Opcodes: (@ address)
+/// Opcodes: (@ address, shown for 6-bit addresses; the extended instructions
+/// always use the two highest address bits)
 /// - 0b11: ERASE at address (set all bits to "1")
 /// - 0b00 @ 0b00????: EWDS (erase/write disable), no DATA
 /// - 0b00 @ 0b01????: WRAL (write all), DATA (for what?)
@@ -19,10 +22,40 @@
 /// - 0b00 @ 0b11????: EWEN (erase/write enable), no DATA
 /// - 0b10: READ 16-bits from address, recv DATA
 /// - 0b01: WRITE 16-bits to address, send DATA
+///
+/// With the ORG pin low (x8 organization) the DATA phase is 8 bits and the
+/// address has one more bit (selecting the byte).
 
+mod decoder;
+mod file;
+mod geometry;
 mod hardware;
 mod low_level;
 mod operations;
+mod simulator;
+mod stats;
+mod timeout;
+mod timing;
+mod trace;
+
+pub use self::decoder::{
+	Frame,
+	Instruction,
+	decode,
+};
+
+pub use self::file::{
+	DumpFormat,
+	FileEeprom,
+	read_dump,
+	write_dump,
+};
+
+pub use self::geometry::{
+	Geometry,
+	Organization,
+	Part,
+};
 
 pub use self::hardware::{
 	Hardware,
@@ -33,4 +66,40 @@ use self::low_level::LowLevel;
 
 pub use self::operations::{
 	HardwareOperations,
+	Microwire,
+};
+
+pub use self::simulator::{
+	Simulator,
+	SimulatorTiming,
+	Violation,
+};
+
+pub use self::stats::{
+	Counters,
+	OperationStats,
+	Statistics,
+};
+
+pub use self::timeout::{
+	Operation,
+	Timeout,
+	Timeouts,
+};
+
+pub use self::timing::{
+	ClockRate,
+	DatasheetTiming,
+	DelayStrategy,
+	EdgeDelay,
+	busy_wait,
+	measure_clock_rate,
+};
+
+pub use self::trace::{
+	Sample,
+	Trace,
+	parse_csv,
+	parse_vcd,
+	write_vcd,
 };
@@ -1,14 +1,29 @@
+use crate::eeprom::{
+	Capacity,
+	Eeprom,
+};
+
 use super::{
+	Geometry,
 	Hardware,
+	Operation,
+	Organization,
 	LowLevel,
+	Timeouts,
 	low_level::ReadTransaction,
+	timeout::Deadline,
 };
 
-const ADDRESS_WIDTH: usize = 6;
-const ADDRESS_LIMIT: usize = (1usize << ADDRESS_WIDTH);
+// extended instructions (opcode 0b00), selected by the two highest address bits
+const EXTENDED_EWDS: u16 = 0b00;
+const EXTENDED_WRAL: u16 = 0b01;
+const EXTENDED_ERAL: u16 = 0b10;
+const EXTENDED_EWEN: u16 = 0b11;
 
+/// Sequential read; yields bytes (in the low 8 bits) in x8 organization
 pub struct Reader<'a, H: Hardware + ?Sized + 'a> {
 	remaining: usize,
+	word_bits: usize,
 	transaction: ReadTransaction<'a, H>,
 }
 
@@ -20,72 +35,92 @@ impl<'a, H: Hardware + ?Sized> Iterator for Reader<'a, H> {
 			return None;
 		}
 		self.remaining -= 1;
-		Some(self.transaction.receive_word())
+		Some(self.transaction.receive_bits(self.word_bits))
 	}
 }
 
-mod inner {
-	use super::*;
+// start a READ at address 0 and count the address bits until the chip
+// answers with the dummy zero bit
+fn read_unknown_address_width<H: Hardware + ?Sized>(hardware: &mut H, timeouts: Timeouts) -> crate::AResult<(ReadTransaction<'_, H>, usize)> {
+	let operation = Operation::DetectAddressWidth;
+	let mut tx = hardware.start_transaction(operation, timeouts.write_cycle)?;
+	let deadline = Deadline::new(operation, timeouts.detect_address_width, tx.now());
 
-	pub trait HardwareOperationsBase {
-		type Hardware: Hardware + ?Sized;
+	tx.send_bit(true)?;
+	tx.send_bit(false)?;
 
-		fn hardware(&mut self) -> &mut Self::Hardware;
-
-		fn read_unknown_address_width(&mut self) -> crate::AResult<(ReadTransaction<Self::Hardware>, usize)> {
-			let mut tx = self.hardware().start_transaction();
+	let (mut tx, first_bit) = tx.force_receive();
 
-			tx.send_bit(true)?;
-			tx.send_bit(false)?;
+	let mut len = 0usize;
 
-			let (mut tx, first_bit) = tx.force_receive();
+	if first_bit {
+		len += 1;
+		while tx.receive_bit() {
+			deadline.check(tx.now())?;
+			len += 1;
+			ensure!(len <= 16, "only detecting address width up to 16 bits allowed to prevent endless loop");
+		}
+	}
 
-			let mut len = 0usize;
+	Ok((tx, len))
+}
 
-			if first_bit {
-				len += 1;
-				while tx.receive_bit() {
-					len += 1;
-					ensure!(len <= 16, "only detecting address width up to 16 bits allowed to prevent endless loop");
-				}
-			}
+mod inner {
+	use super::*;
 
-			Ok((tx, len))
-		}
-	}
+	pub trait HardwareOperationsBase {
+		type Hardware: Hardware + ?Sized;
 
-	impl<H: Hardware + ?Sized> HardwareOperationsBase for H {
-		type Hardware = H;
+		fn hardware(&mut self) -> &mut Self::Hardware;
 
+		fn timeouts(&self) -> Timeouts;
 
-		fn hardware(&mut self) -> &mut Self::Hardware {
-			self
+		fn read_unknown_address_width(&mut self) -> crate::AResult<(ReadTransaction<Self::Hardware>, usize)> {
+			let timeouts = self.timeouts();
+			read_unknown_address_width(self.hardware(), timeouts)
 		}
 	}
 }
 
 pub trait HardwareOperations: inner::HardwareOperationsBase {
+	fn geometry(&self) -> Geometry;
+
 	fn erase(&mut self, address: usize) -> crate::AResult<()> {
-		assert!(address < ADDRESS_LIMIT);
-		let mut tx = self.hardware().start_program_transaction();
+		let geometry = self.geometry();
+		assert!(address < geometry.words());
+		let timeout = self.timeouts().write_cycle;
+		let mut tx = self.hardware().start_program_transaction(Operation::Erase(address), timeout)?;
 		tx.send_bit(true)?;
 		tx.send_bit(true)?;
-		tx.send_bits(address as u16, ADDRESS_WIDTH)
+		tx.send_bits(address as u16, geometry.address_bits())?;
+		tx.finish()
 	}
 
 	fn erase_all(&mut self) -> crate::AResult<()> {
-		let mut tx = self.hardware().start_program_transaction();
-		tx.send_bits(0b00_10_0000, 8)
+		let geometry = self.geometry();
+		let timeout = self.timeouts().write_cycle;
+		let mut tx = self.hardware().start_program_transaction(Operation::EraseAll, timeout)?;
+		tx.send_bits(0b00, 2)?;
+		tx.send_bits(geometry.extended_address(EXTENDED_ERAL), geometry.address_bits())?;
+		tx.finish()
 	}
 
 	fn erase_write_disable(&mut self) -> crate::AResult<()> {
-		let mut tx = self.hardware().start_program_transaction();
-		tx.send_bits(0b00_00_0000, 8)
+		let geometry = self.geometry();
+		let timeout = self.timeouts().write_cycle;
+		let mut tx = self.hardware().start_program_transaction(Operation::EraseWriteDisable, timeout)?;
+		tx.send_bits(0b00, 2)?;
+		tx.send_bits(geometry.extended_address(EXTENDED_EWDS), geometry.address_bits())?;
+		tx.finish()
 	}
 
 	fn erase_write_enable(&mut self) -> crate::AResult<()> {
-		let mut tx = self.hardware().start_program_transaction();
-		tx.send_bits(0b00_11_0000, 8)
+		let geometry = self.geometry();
+		let timeout = self.timeouts().write_cycle;
+		let mut tx = self.hardware().start_program_transaction(Operation::EraseWriteEnable, timeout)?;
+		tx.send_bits(0b00, 2)?;
+		tx.send_bits(geometry.extended_address(EXTENDED_EWEN), geometry.address_bits())?;
+		tx.finish()
 	}
 
 	fn detect_address_width(&mut self) -> crate::AResult<usize> {
@@ -94,58 +129,265 @@ pub trait HardwareOperations: inner::HardwareOperationsBase {
 	}
 
 	fn read(&mut self, address: usize) -> crate::AResult<u16> {
-		assert!(address < ADDRESS_LIMIT);
-		let mut tx = self.hardware().start_transaction();
+		let geometry = self.geometry();
+		assert!(address < geometry.words());
+		let timeout = self.timeouts().write_cycle;
+		let mut tx = self.hardware().start_transaction(Operation::Read(address), timeout)?;
 
 		tx.send_bit(true)?;
 		tx.send_bit(false)?;
-		tx.send_bits(address as u16, ADDRESS_WIDTH)?;
+		tx.send_bits(address as u16, geometry.address_bits())?;
 		let mut tx = tx.start_receive()?;
-		let result = tx.receive_word();
+		let result = tx.receive_bits(geometry.word_bits());
 
 		Ok(result)
 	}
 
+	fn read_byte(&mut self, address: usize) -> crate::AResult<u8> {
+		ensure!(self.geometry().organization() == Organization::X8, "EEPROM not in x8 organization");
+		Ok(self.read(address)? as u8)
+	}
+
+	// sequential read of the whole EEPROM in x8 organization
+	fn read_all_bytes(&mut self) -> crate::AResult<Vec<u8>> {
+		ensure!(self.geometry().organization() == Organization::X8, "EEPROM not in x8 organization");
+		Ok(self.read_all()?.map(|byte| byte as u8).collect())
+	}
+
+	// sequential read of the whole EEPROM, starting at address 0
 	fn read_all(&mut self) -> crate::AResult<Reader<Self::Hardware>> {
-		let (transaction, len) = self.read_unknown_address_width()?;
+		let geometry = self.geometry();
+		let timeout = self.timeouts().write_cycle;
+		let mut tx = self.hardware().start_transaction(Operation::Read(0), timeout)?;
+
+		tx.send_bit(true)?;
+		tx.send_bit(false)?;
+		tx.send_bits(0, geometry.address_bits())?;
 
 		Ok(Reader {
-			remaining: 1 << len,
-			transaction,
+			remaining: geometry.words(),
+			word_bits: geometry.word_bits(),
+			transaction: tx.start_receive()?,
 		})
 	}
 
+	// read the whole EEPROM `reads` times and take the bitwise majority;
+	// addresses that didn't read the same every time are logged as warnings
+	fn read_all_consensus(&mut self, reads: usize) -> crate::AResult<Vec<u16>> {
+		ensure!(reads % 2 == 1, "Need an odd number of reads for a majority, got {}", reads);
+		let mut passes = Vec::with_capacity(reads);
+		for _ in 0..reads {
+			passes.push(self.read_all()?.collect::<Vec<u16>>());
+		}
+		let words = (0..self.geometry().words()).map(|address| {
+			let values: Vec<u16> = passes.iter().map(|pass| pass[address]).collect();
+			let consensus = (0..16).filter(|&bit| {
+				values.iter().filter(|&&value| value & (1 << bit) != 0).count() > reads / 2
+			}).fold(0u16, |word, bit| word | 1 << bit);
+			if values.iter().any(|&value| value != consensus) {
+				warn!("Unstable EEPROM word at {:02x}: read {:04x?}, using {:04x}", address, values, consensus);
+			}
+			consensus
+		}).collect();
+		Ok(words)
+	}
+
 	fn write(&mut self, address: usize, word: u16) -> crate::AResult<()> {
-		assert!(address < ADDRESS_LIMIT);
-		let mut tx = self.hardware().start_program_transaction();
+		let geometry = self.geometry();
+		assert!(address < geometry.words());
+		let timeout = self.timeouts().write_cycle;
+		let mut tx = self.hardware().start_program_transaction(Operation::Write(address), timeout)?;
 		tx.send_bit(false)?;
 		tx.send_bit(true)?;
-		tx.send_bits(address as u16, ADDRESS_WIDTH)?;
-		tx.send_bits(word, 16)
+		tx.send_bits(address as u16, geometry.address_bits())?;
+		tx.send_bits(word, geometry.word_bits())?;
+		tx.finish()
+	}
+
+	fn write_byte(&mut self, address: usize, byte: u8) -> crate::AResult<()> {
+		ensure!(self.geometry().organization() == Organization::X8, "EEPROM not in x8 organization");
+		self.write(address, u16::from(byte))
 	}
 
 	// write one word into all addresses; includes erasing beforre
 	fn write_all(&mut self, word: u16) -> crate::AResult<()> {
-		let mut tx = self.hardware().start_program_transaction();
-		tx.send_bits(0b00_01_0000, 8)?;
-		tx.send_bits(word, 16)
+		let geometry = self.geometry();
+		let timeout = self.timeouts().write_cycle;
+		let mut tx = self.hardware().start_program_transaction(Operation::WriteAll, timeout)?;
+		tx.send_bits(0b00, 2)?;
+		tx.send_bits(geometry.extended_address(EXTENDED_WRAL), geometry.address_bits())?;
+		tx.send_bits(word, geometry.word_bits())?;
+		tx.finish()
+	}
+
+	// read the whole EEPROM; returns all words not erased (all bits set)
+	fn blank_check(&mut self) -> crate::AResult<Vec<(usize, u16)>> {
+		let erased = (!0u32 >> (32 - self.geometry().word_bits())) as u16;
+		Ok(self.read_all()?.enumerate().filter(|&(_, word)| word != erased).collect())
+	}
+
+	// write one word into all addresses (ERAL + WRAL; not all parts erase
+	// before WRAL) and verify by reading everything back
+	fn fill(&mut self, word: u16) -> crate::AResult<()> {
+		{
+			let mut prog = self.start_programming()?;
+			crate::signal::check_interrupted()?;
+			prog.erase_all()?;
+			prog.write_all(word)?;
+		}
+		let mismatches: Vec<(usize, u16)> = self.read_all()?.enumerate().filter(|&(_, w)| w != word).collect();
+		if let Some(&(address, first)) = mismatches.first() {
+			bail!("Verify failed for {} words (first at {:02x}: expected {:04x}, EEPROM has {:04x})",
+				mismatches.len(), address, word, first);
+		}
+		Ok(())
+	}
+
+	// program `data` starting at address 0, but only erase and write words
+	// that differ from the current content (each verified); words after
+	// `data` are left alone. returns the number of changed words.
+	fn write_differential(&mut self, data: &[u16]) -> crate::AResult<usize> {
+		let geometry = self.geometry();
+		ensure!(data.len() <= geometry.words(), "{} words don't fit into EEPROM ({})", data.len(), geometry);
+		let current: Vec<u16> = self.read_all()?.take(data.len()).collect();
+		let changed: Vec<usize> = (0..data.len()).filter(|&address| current[address] != data[address]).collect();
+		if changed.is_empty() {
+			return Ok(0);
+		}
+
+		let mut prog = self.start_programming()?;
+		for &address in &changed {
+			// not between erase and write
+			crate::signal::check_interrupted()?;
+			prog.erase(address)?;
+			prog.write(address, data[address])?;
+			let word = prog.read(address)?;
+			ensure!(word == data[address],
+				"Verify failed at {:02x}: expected {:04x}, EEPROM has {:04x}", address, data[address], word
+			);
+		}
+		Ok(changed.len())
 	}
 
 	fn start_programming(&mut self) -> crate::AResult<ProgrammingEnabled<Self>>;
 }
 
-impl<H: ?Sized+Hardware> HardwareOperations for H {
+/// Microwire EEPROM connected to `Hardware` pins
+pub struct Microwire<H: Hardware> {
+	hardware: H,
+	geometry: Geometry,
+	timeouts: Timeouts,
+}
+
+impl<H: Hardware> Microwire<H> {
+	pub fn new(hardware: H, geometry: Geometry) -> Self {
+		Microwire {
+			hardware,
+			geometry,
+			timeouts: Timeouts::default(),
+		}
+	}
+
+	/// Detect the address width and 16-bit organization; see
+	/// `detect_with_timeouts` for the size
+	pub fn detect(hardware: H) -> crate::AResult<Self> {
+		Microwire::detect_with_organization(hardware, Organization::X16)
+	}
+
+	/// Detect the address width; the organization (ORG pin) can't be detected
+	pub fn detect_with_organization(hardware: H, organization: Organization) -> crate::AResult<Self> {
+		Microwire::detect_with_timeouts(hardware, organization, Timeouts::default())
+	}
+
+	/// Parts ignoring the highest address bit (93C56, 93C76) can't be told
+	/// apart from the next larger one by the address width: if both halves
+	/// of the address space read the same, the smaller part is assumed (the
+	/// larger one would only hide its duplicate upper half). A uniform
+	/// content (e.g. blank) keeps the full size, as a smaller guess would
+	/// make blank checks and fills silently skip half of a larger part.
+	pub fn detect_with_timeouts(mut hardware: H, organization: Organization, timeouts: Timeouts) -> crate::AResult<Self> {
+		let (_, address_bits) = read_unknown_address_width(&mut hardware, timeouts)?;
+		let geometry = Geometry::from_address_bits(address_bits, organization)?;
+		let mut result = Microwire::new(hardware, geometry);
+		result.set_timeouts(timeouts);
+		if let Some(wrapped) = geometry.wrapped() {
+			let words: Vec<u16> = result.read_all()?.collect();
+			let (low, high) = words.split_at(wrapped.words());
+			if low != high {
+				// full size
+			} else if low.iter().all(|&word| word == low[0]) {
+				warn!("EEPROM content uniform, can't detect the size: assuming {}; specify the EEPROM type otherwise", geometry);
+			} else {
+				info!("EEPROM address space wraps around (or both halves are equal): using {}; specify the EEPROM type otherwise", wrapped);
+				result.geometry = wrapped;
+			}
+		}
+		Ok(result)
+	}
+
+	pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+		self.timeouts = timeouts;
+	}
+
+	pub fn into_inner(self) -> H {
+		self.hardware
+	}
+}
+
+impl<H: Hardware> inner::HardwareOperationsBase for Microwire<H> {
+	type Hardware = H;
+
+	fn hardware(&mut self) -> &mut Self::Hardware {
+		&mut self.hardware
+	}
+
+	fn timeouts(&self) -> Timeouts {
+		self.timeouts
+	}
+}
+
+impl<H: Hardware> HardwareOperations for Microwire<H> {
+	fn geometry(&self) -> Geometry {
+		self.geometry
+	}
+
 	fn start_programming(&mut self) -> crate::AResult<ProgrammingEnabled<Self>> {
+		let defer = crate::signal::defer_termination()?;
 		self.erase_write_enable()?;
-		Ok(ProgrammingEnabled(self, true))
+		Ok(ProgrammingEnabled(self, Some(defer)))
+	}
+}
+
+impl<H: Hardware> Eeprom for Microwire<H> {
+	fn capacity(&self) -> Capacity {
+		Capacity {
+			words: self.geometry.words(),
+			word_bits: self.geometry.word_bits(),
+		}
+	}
+
+	fn read_contents(&mut self) -> crate::AResult<Vec<u16>> {
+		Ok(self.read_all()?.collect())
+	}
+
+	fn write_contents(&mut self, data: &[u16]) -> crate::AResult<()> {
+		self.write_differential(data)?;
+		Ok(())
+	}
+
+	fn erase_contents(&mut self) -> crate::AResult<()> {
+		self.start_programming()?.erase_all()
 	}
 }
 
-pub struct ProgrammingEnabled<'a, H: ?Sized+HardwareOperations+'a>(&'a mut H, bool);
+/// Disables erase/write when dropped; termination signals are deferred
+/// until then, and programming loops stop before their next word once one
+/// is pending
+pub struct ProgrammingEnabled<'a, H: ?Sized+HardwareOperations+'a>(&'a mut H, Option<crate::signal::DeferTermination>);
 
 impl<'a, H: ?Sized+HardwareOperations> Drop for ProgrammingEnabled<'a, H> {
 	fn drop(&mut self) {
-		if self.1 {
+		if self.1.is_some() {
 			if let Err(e) = self.0.erase_write_disable() {
 				eprintln!("Couldn't disable Erase/Write mode: {}", e);
 			}
@@ -159,10 +401,18 @@ impl<'a, H: ?Sized+HardwareOperations> inner::HardwareOperationsBase for Program
 	fn hardware(&mut self) -> &mut Self::Hardware {
 		self.0.hardware()
 	}
+
+	fn timeouts(&self) -> Timeouts {
+		self.0.timeouts()
+	}
 }
 
 impl<'a, H: ?Sized+HardwareOperations> HardwareOperations for ProgrammingEnabled<'a, H> {
+	fn geometry(&self) -> Geometry {
+		self.0.geometry()
+	}
+
 	fn start_programming(&mut self) -> crate::AResult<ProgrammingEnabled<Self>> {
-		Ok(ProgrammingEnabled(self, false))
+		Ok(ProgrammingEnabled(self, None))
 	}
 }
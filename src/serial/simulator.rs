@@ -0,0 +1,654 @@
+// Simulated 93Cx6 EEPROM, driven through the `Hardware` pins
+//
+// Models the chip at the pin level:
+// - DI is sampled on rising CLK while CS is high
+// - leading zeros before the start bit are ignored; while the chip is BUSY
+//   (and CS is high) DO is LOW, otherwise it is pulled up (HIGH)
+// - READ: a dummy zero follows the address, then the data bits are shifted
+//   out on rising CLK; the address increments after each word (sequential
+//   read, wrapping around)
+// - ERASE/WRITE/ERAL/WRAL start their self-timed cycle when CS drops after a
+//   complete instruction; they are ignored unless EWEN was sent before
+//
+// Time is virtual: each `delay()` advances it by one clock edge; `now()`
+// follows the virtual time (for timeouts).
+
+use std::fmt;
+use std::time::{
+	Duration,
+	Instant,
+};
+
+use failure::Fail;
+
+use super::{
+	Geometry,
+	Hardware,
+	OutPins,
+};
+
+/// Timing parameters of the simulated chip
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SimulatorTiming {
+	/// virtual time passing in each `Hardware::delay` call
+	pub delay: Duration,
+	/// DO changes this long after the rising CLK edge (tPD)
+	pub output_delay: Duration,
+	/// READY/BUSY status is valid this long after CS goes high (tSV)
+	pub status_valid: Duration,
+	/// duration of the self-timed erase/write cycle (tWC)
+	pub write_cycle: Duration,
+}
+
+impl Default for SimulatorTiming {
+	// Microchip 93C46B at 4.5V..5.5V
+	fn default() -> Self {
+		SimulatorTiming {
+			delay: Duration::from_nanos(250),
+			output_delay: Duration::from_nanos(400),
+			status_valid: Duration::from_nanos(500),
+			write_cycle: Duration::from_millis(2),
+		}
+	}
+}
+
+/// Protocol violation detected by the simulator
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Violation {
+	/// virtual time since the simulator was created
+	pub time: Duration,
+	pub message: String,
+}
+
+impl fmt::Display for Violation {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "EEPROM protocol violation at {:?}: {}", self.time, self.message)
+	}
+}
+
+impl Fail for Violation {
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Instruction {
+	Write(usize, u16),
+	Erase(usize),
+	EraseWriteEnable,
+	EraseWriteDisable,
+	EraseAll,
+	WriteAll(u16),
+}
+
+impl fmt::Display for Instruction {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Instruction::Write(address, data) => write!(f, "WRITE @{:02x} {:04x}", address, data),
+			Instruction::Erase(address) => write!(f, "ERASE @{:02x}", address),
+			Instruction::EraseWriteEnable => write!(f, "EWEN"),
+			Instruction::EraseWriteDisable => write!(f, "EWDS"),
+			Instruction::EraseAll => write!(f, "ERAL"),
+			Instruction::WriteAll(data) => write!(f, "WRAL {:04x}", data),
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum State {
+	// CS low
+	Standby,
+	// CS high, waiting for start bit
+	Idle,
+	Opcode { opcode: u16, bits: usize },
+	Address { opcode: u16, address: usize, bits: usize },
+	// WRITE/WRAL data
+	Data { opcode: u16, address: usize, data: u16, bits: usize },
+	// waiting for CS to drop
+	Complete(Instruction),
+	Reading { address: usize, remaining: usize },
+	// after a violation: ignore everything until CS drops
+	Ignore,
+}
+
+/// Simulated Microwire EEPROM of the 93Cx6 family
+pub struct Simulator {
+	geometry: Geometry,
+	timing: SimulatorTiming,
+	memory: Vec<u16>,
+	write_enabled: bool,
+	epoch: Instant,
+	now: Duration,
+	busy_until: Duration,
+	pins: OutPins,
+	state: State,
+	chip_selected_at: Duration,
+	// DO: previous value is visible until the new value becomes valid
+	output: bool,
+	previous_output: bool,
+	output_valid_at: Duration,
+	violations: Vec<Violation>,
+}
+
+impl Simulator {
+	/// Erased (all bits set) EEPROM with default timing
+	pub fn new(geometry: Geometry) -> Self {
+		Simulator::with_timing(geometry, SimulatorTiming::default())
+	}
+
+	pub fn with_timing(geometry: Geometry, timing: SimulatorTiming) -> Self {
+		let erased = Simulator::word_mask(geometry);
+		Simulator {
+			geometry,
+			timing,
+			memory: vec![erased; geometry.words()],
+			write_enabled: false,
+			epoch: Instant::now(),
+			now: Duration::from_secs(0),
+			busy_until: Duration::from_secs(0),
+			pins: OutPins { chip_select: false, clock: false, data: false },
+			state: State::Standby,
+			chip_selected_at: Duration::from_secs(0),
+			output: true,
+			previous_output: true,
+			output_valid_at: Duration::from_secs(0),
+			violations: Vec::new(),
+		}
+	}
+
+	fn word_mask(geometry: Geometry) -> u16 {
+		(!0u32 >> (32 - geometry.word_bits())) as u16
+	}
+
+	pub fn geometry(&self) -> Geometry {
+		self.geometry
+	}
+
+	pub fn timing(&self) -> SimulatorTiming {
+		self.timing
+	}
+
+	/// Memory content (bytes in the low bits for x8 organization)
+	pub fn memory(&self) -> &[u16] {
+		&self.memory
+	}
+
+	/// Replace memory content; missing words are erased, extra words ignored
+	pub fn load(&mut self, data: &[u16]) {
+		let mask = Simulator::word_mask(self.geometry);
+		for (address, word) in self.memory.iter_mut().enumerate() {
+			*word = data.get(address).map(|w| w & mask).unwrap_or(mask);
+		}
+	}
+
+	pub fn is_write_enabled(&self) -> bool {
+		self.write_enabled
+	}
+
+	pub fn is_busy(&self) -> bool {
+		self.now < self.busy_until
+	}
+
+	/// Virtual time since the simulator was created
+	pub fn elapsed(&self) -> Duration {
+		self.now
+	}
+
+	pub fn violations(&self) -> &[Violation] {
+		&self.violations
+	}
+
+	/// Fail with the first recorded violation (and forget all of them)
+	pub fn check(&mut self) -> crate::AResult<()> {
+		if self.violations.is_empty() {
+			return Ok(());
+		}
+		let count = self.violations.len();
+		let first = self.violations.drain(..).next().unwrap();
+		if count > 1 {
+			let msg = format!("{} (and {} more)", first, count - 1);
+			return Err(first.context(msg).into());
+		}
+		Err(first.into())
+	}
+
+	fn violation(&mut self, message: String) {
+		debug!("EEPROM simulator: {}", message);
+		self.violations.push(Violation {
+			time: self.now,
+			message,
+		});
+	}
+
+	fn set_output(&mut self, value: bool, delay: Duration) {
+		self.previous_output = self.visible_output();
+		self.output = value;
+		self.output_valid_at = self.now + delay;
+	}
+
+	fn visible_output(&self) -> bool {
+		if self.now >= self.output_valid_at {
+			self.output
+		} else {
+			self.previous_output
+		}
+	}
+
+	fn chip_select(&mut self) {
+		self.state = State::Idle;
+		self.chip_selected_at = self.now;
+	}
+
+	fn chip_deselect(&mut self) {
+		match self.state {
+			State::Standby | State::Idle | State::Reading { .. } | State::Ignore => (),
+			State::Complete(instruction) => self.execute(instruction),
+			State::Opcode { .. } | State::Address { .. } | State::Data { .. } => {
+				self.violation(format!("CS dropped during incomplete instruction ({:?})", self.state));
+			},
+		}
+		self.state = State::Standby;
+		// DO goes to high impedance (pulled up)
+		self.set_output(true, Duration::from_secs(0));
+	}
+
+	fn execute(&mut self, instruction: Instruction) {
+		let mask = Simulator::word_mask(self.geometry);
+		match instruction {
+			Instruction::EraseWriteEnable => self.write_enabled = true,
+			Instruction::EraseWriteDisable => self.write_enabled = false,
+			_ if !self.write_enabled => {
+				self.violation(format!("{} ignored: erase/write not enabled", instruction));
+			},
+			Instruction::Write(address, data) => {
+				self.memory[address] = data;
+				self.busy_until = self.now + self.timing.write_cycle;
+			},
+			Instruction::Erase(address) => {
+				self.memory[address] = mask;
+				self.busy_until = self.now + self.timing.write_cycle;
+			},
+			Instruction::EraseAll => {
+				for word in &mut self.memory {
+					*word = mask;
+				}
+				self.busy_until = self.now + self.timing.write_cycle;
+			},
+			Instruction::WriteAll(data) => {
+				for word in &mut self.memory {
+					*word = data;
+				}
+				self.busy_until = self.now + self.timing.write_cycle;
+			},
+		}
+	}
+
+	// addresses beyond the size wrap around (e.g. 93C56 ignores the highest bit)
+	fn wrap_address(&self, address: usize) -> usize {
+		address % self.geometry.words()
+	}
+
+	fn clock_rising(&mut self, data: bool) {
+		let address_bits = self.geometry.address_bits();
+		let word_bits = self.geometry.word_bits();
+		self.state = match self.state {
+			State::Standby | State::Ignore => self.state,
+			State::Idle if !data => State::Idle, // leading zeros
+			State::Idle => {
+				if self.is_busy() {
+					self.violation("start bit while erase/write cycle in progress".into());
+					State::Ignore
+				} else {
+					// DO: high impedance (pulled up) until READ data
+					self.set_output(true, self.timing.output_delay);
+					State::Opcode { opcode: 0, bits: 0 }
+				}
+			},
+			State::Opcode { opcode, bits } => {
+				let opcode = opcode << 1 | data as u16;
+				if bits + 1 < 2 {
+					State::Opcode { opcode, bits: bits + 1 }
+				} else {
+					State::Address { opcode, address: 0, bits: 0 }
+				}
+			},
+			State::Address { opcode, address, bits } => {
+				let address = address << 1 | data as usize;
+				if bits + 1 < address_bits {
+					State::Address { opcode, address, bits: bits + 1 }
+				} else {
+					self.decode(opcode, address)
+				}
+			},
+			State::Data { opcode, address, data: word, bits } => {
+				let word = word << 1 | data as u16;
+				if bits + 1 < word_bits {
+					State::Data { opcode, address, data: word, bits: bits + 1 }
+				} else if 0b01 == opcode {
+					State::Complete(Instruction::Write(address, word))
+				} else {
+					State::Complete(Instruction::WriteAll(word))
+				}
+			},
+			State::Complete(instruction) => {
+				self.violation(format!("clock after complete instruction {}", instruction));
+				State::Ignore
+			},
+			State::Reading { address, remaining } => {
+				let (address, remaining) = if 0 == remaining {
+					(self.wrap_address(address + 1), word_bits)
+				} else {
+					(address, remaining)
+				};
+				let remaining = remaining - 1;
+				let bit = 0 != self.memory[address] & (1 << remaining);
+				self.set_output(bit, self.timing.output_delay);
+				State::Reading { address, remaining }
+			},
+		};
+	}
+
+	// full address received
+	fn decode(&mut self, opcode: u16, address: usize) -> State {
+		let address_bits = self.geometry.address_bits();
+		match opcode {
+			0b10 => {
+				// dummy zero before data
+				self.set_output(false, self.timing.output_delay);
+				State::Reading { address: self.wrap_address(address), remaining: self.geometry.word_bits() }
+			},
+			0b01 => State::Data { opcode, address: self.wrap_address(address), data: 0, bits: 0 },
+			0b11 => State::Complete(Instruction::Erase(self.wrap_address(address))),
+			_ => match address >> (address_bits - 2) {
+				0b00 => State::Complete(Instruction::EraseWriteDisable),
+				0b01 => State::Data { opcode, address: 0, data: 0, bits: 0 },
+				0b10 => State::Complete(Instruction::EraseAll),
+				_ => State::Complete(Instruction::EraseWriteEnable),
+			},
+		}
+	}
+}
+
+impl Hardware for Simulator {
+	fn set_pins(&mut self, pins: OutPins) {
+		let old = self.pins;
+		self.pins = pins;
+
+		if old.chip_select && !pins.chip_select {
+			self.chip_deselect();
+			return;
+		}
+		if !pins.chip_select {
+			return;
+		}
+		let rising = !old.clock && pins.clock;
+		if !old.chip_select {
+			if rising {
+				self.violation("CS and CLK rising at the same time".into());
+			}
+			self.chip_select();
+			return;
+		}
+		if rising {
+			if old.data != pins.data {
+				self.violation("DI changed on rising CLK edge".into());
+			}
+			self.clock_rising(pins.data);
+		}
+	}
+
+	fn read_pin(&mut self) -> bool {
+		if !self.pins.chip_select {
+			// high impedance, pulled up
+			return true;
+		}
+		if let State::Idle = self.state {
+			if self.now < self.chip_selected_at + self.timing.status_valid {
+				self.violation("READY/BUSY status read before it is valid".into());
+			}
+			return !self.is_busy();
+		}
+		if self.now < self.output_valid_at {
+			self.violation("DO read before output is valid".into());
+		}
+		self.visible_output()
+	}
+
+	fn delay(&mut self) {
+		self.now += self.timing.delay;
+	}
+
+	fn now(&self) -> Instant {
+		self.epoch + self.now
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use super::{
+		Simulator,
+		SimulatorTiming,
+	};
+	use crate::serial::{
+		Hardware,
+		HardwareOperations,
+		Microwire,
+		Operation,
+		OutPins,
+		Organization,
+		Part,
+		Timeout,
+		Timeouts,
+	};
+
+	#[test]
+	fn detect_address_width() {
+		for &part in &Part::ALL {
+			for &organization in &[Organization::X8, Organization::X16] {
+				let geometry = part.geometry_with_organization(organization);
+				// the halves of the memory differ
+				let mut sim = Simulator::new(geometry);
+				let data: Vec<u16> = (0..geometry.words()).map(|address| (address >= geometry.words() / 2) as u16).collect();
+				sim.load(&data);
+				let ee = Microwire::detect_with_organization(sim, organization).unwrap();
+				assert_eq!(ee.geometry(), geometry, "{} {:?}", part, organization);
+				ee.into_inner().check().unwrap();
+			}
+		}
+
+		// a blank 93C56 can't be told from a 93C66: keep the full size
+		let ee = Microwire::detect(Simulator::new(Part::C66.geometry())).unwrap();
+		assert_eq!(ee.geometry(), Part::C66.geometry());
+		let ee = Microwire::detect(Simulator::new(Part::C56.geometry())).unwrap();
+		assert_eq!(ee.geometry(), Part::C66.geometry());
+	}
+
+	#[test]
+	fn write_and_read() {
+		let geometry = Part::C66.geometry();
+		let mut ee = Microwire::new(Simulator::new(geometry), geometry);
+		{
+			let mut prog = ee.start_programming().unwrap();
+			prog.write(0x12, 0xa55a).unwrap();
+			prog.write(0x13, 0x1234).unwrap();
+			prog.erase(0x12).unwrap();
+		}
+		assert_eq!(ee.read(0x12).unwrap(), 0xffff);
+		assert_eq!(ee.read(0x13).unwrap(), 0x1234);
+		let words: Vec<u16> = ee.read_all().unwrap().collect();
+		assert_eq!(words.len(), 256);
+		assert_eq!(words[0x13], 0x1234);
+
+		let sim = ee.into_inner();
+		assert!(!sim.is_write_enabled());
+		// three self-timed cycles
+		assert!(sim.elapsed() >= 3 * sim.timing().write_cycle);
+		assert!(sim.violations().is_empty());
+	}
+
+	#[test]
+	fn x8_organization() {
+		let geometry = Part::C46.geometry_with_organization(Organization::X8);
+		let mut ee = Microwire::new(Simulator::new(geometry), geometry);
+		{
+			let mut prog = ee.start_programming().unwrap();
+			prog.write_all(0x00).unwrap();
+			prog.write_byte(0x7f, 0x42).unwrap();
+		}
+		let bytes = ee.read_all_bytes().unwrap();
+		assert_eq!(bytes.len(), 128);
+		assert_eq!(bytes[0x7e], 0x00);
+		assert_eq!(bytes[0x7f], 0x42);
+		ee.into_inner().check().unwrap();
+	}
+
+	#[test]
+	fn write_cycle_timeout() {
+		let geometry = Part::C46.geometry();
+		let timing = SimulatorTiming {
+			write_cycle: Duration::from_millis(50),
+			..SimulatorTiming::default()
+		};
+		let mut ee = Microwire::new(Simulator::with_timing(geometry, timing), geometry);
+		let mut prog = ee.start_programming().unwrap();
+		let e = prog.write(0x05, 0x1234).unwrap_err();
+		let timeout = e.downcast::<Timeout>().unwrap();
+		assert_eq!(timeout.operation, Operation::Write(0x05));
+		assert_eq!(timeout.timeout, Timeouts::default().write_cycle);
+	}
+
+	#[test]
+	fn blank_check_and_fill() {
+		let geometry = Part::C56.geometry();
+		let mut sim = Simulator::new(geometry);
+		sim.load(&[0xffff, 0x0000, 0xffff, 0x1234]);
+		let mut ee = Microwire::new(sim, geometry);
+		assert_eq!(ee.blank_check().unwrap(), vec![(1, 0x0000), (3, 0x1234)]);
+
+		ee.fill(0xa5a5).unwrap();
+		assert_eq!(ee.blank_check().unwrap().len(), 128);
+		ee.fill(0xffff).unwrap();
+		assert!(ee.blank_check().unwrap().is_empty());
+		ee.into_inner().check().unwrap();
+	}
+
+	#[test]
+	fn write_without_enable() {
+		let geometry = Part::C46.geometry();
+		let mut ee = Microwire::new(Simulator::new(geometry), geometry);
+		ee.write(0, 0x1234).unwrap();
+		assert_eq!(ee.read(0).unwrap(), 0xffff);
+		let mut sim = ee.into_inner();
+		assert_eq!(sim.violations().len(), 1);
+		assert!(sim.check().is_err());
+		assert!(sim.check().is_ok());
+	}
+
+	// flips the DO reads in `flip`
+	struct Noisy {
+		sim: Simulator,
+		reads: usize,
+		flip: std::ops::Range<usize>,
+	}
+
+	impl Hardware for Noisy {
+		fn set_pins(&mut self, pins: OutPins) {
+			self.sim.set_pins(pins)
+		}
+
+		fn read_pin(&mut self) -> bool {
+			self.reads += 1;
+			self.sim.read_pin() ^ self.flip.contains(&self.reads)
+		}
+
+		fn delay(&mut self) {
+			self.sim.delay()
+		}
+
+		fn now(&self) -> std::time::Instant {
+			self.sim.now()
+		}
+	}
+
+	#[test]
+	fn consensus_read() {
+		let geometry = Part::C46.geometry();
+		let data: Vec<u16> = (0..64).map(|i| i * 0x0101).collect();
+		let noisy = |flip| {
+			let mut sim = Simulator::new(geometry);
+			sim.load(&data);
+			Microwire::new(Noisy { sim, reads: 0, flip }, geometry)
+		};
+		let mut ee = noisy(500..503);
+		assert_ne!(ee.read_all().unwrap().collect::<Vec<u16>>(), data);
+
+		// a full sequential read takes a bit over 1024 DO reads
+		let mut ee = noisy(1500..1503);
+		assert!(ee.read_all_consensus(2).is_err());
+		assert_eq!(ee.read_all_consensus(3).unwrap(), data);
+		ee.into_inner().sim.check().unwrap();
+	}
+
+	// a termination signal arrives while the `interrupt_at`-th operation
+	// matching `interrupt` starts
+	struct Interrupting {
+		sim: Simulator,
+		interrupt: fn(Operation) -> bool,
+		interrupt_at: usize,
+		operations: usize,
+	}
+
+	impl Hardware for Interrupting {
+		fn set_pins(&mut self, pins: OutPins) {
+			self.sim.set_pins(pins)
+		}
+
+		fn read_pin(&mut self) -> bool {
+			self.sim.read_pin()
+		}
+
+		fn delay(&mut self) {
+			self.sim.delay()
+		}
+
+		fn now(&self) -> std::time::Instant {
+			self.sim.now()
+		}
+
+		fn begin_operation(&mut self, operation: Operation) {
+			if (self.interrupt)(operation) {
+				self.operations += 1;
+				if self.operations == self.interrupt_at {
+					crate::signal::set_test_pending(libc::SIGINT);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn interrupted_programming() {
+		let geometry = Part::C46.geometry();
+		let old: Vec<u16> = (0..8).collect();
+		let new: Vec<u16> = (0..8).map(|i| 0x1100 + i).collect();
+		let mut sim = Simulator::new(geometry);
+		sim.load(&old);
+		let is_erase = |operation| matches!(operation, Operation::Erase(_));
+		let mut ee = Microwire::new(Interrupting { sim, interrupt: is_erase, interrupt_at: 3, operations: 0 }, geometry);
+		let e = ee.write_differential(&new).unwrap_err();
+		crate::signal::set_test_pending(0);
+		assert_eq!(e.to_string(), "Interrupted by SIGINT");
+		// the word being erased got written
+		let words: Vec<u16> = ee.read_all().unwrap().take(8).collect();
+		assert_eq!(words, [0x1100, 0x1101, 0x1102, 3, 4, 5, 6, 7]);
+		ee.into_inner().sim.check().unwrap();
+
+		// ERAL is followed by WRAL
+		let is_erase_all = |operation| operation == Operation::EraseAll;
+		let sim = Simulator::new(geometry);
+		let mut ee = Microwire::new(Interrupting { sim, interrupt: is_erase_all, interrupt_at: 1, operations: 0 }, geometry);
+		ee.fill(0x1234).unwrap();
+		crate::signal::set_test_pending(0);
+		assert!(ee.blank_check().unwrap().iter().all(|&(_, word)| word == 0x1234));
+		assert!(ee.fill(0xffff).is_ok());
+	}
+}
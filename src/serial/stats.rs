@@ -0,0 +1,194 @@
+use std::fmt;
+use std::time::{
+	Duration,
+	Instant,
+};
+
+use super::{
+	Hardware,
+	Operation,
+	OutPins,
+};
+
+/// Bit-bang activity
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Counters {
+	/// rising CLK edges
+	pub clock_cycles: usize,
+	pub pin_writes: usize,
+	pub pin_reads: usize,
+	pub delays: usize,
+	/// READY/BUSY status reads returning BUSY
+	pub busy_polls: usize,
+}
+
+impl Counters {
+	fn since(&self, start: &Counters) -> Counters {
+		Counters {
+			clock_cycles: self.clock_cycles - start.clock_cycles,
+			pin_writes: self.pin_writes - start.pin_writes,
+			pin_reads: self.pin_reads - start.pin_reads,
+			delays: self.delays - start.delays,
+			busy_polls: self.busy_polls - start.busy_polls,
+		}
+	}
+}
+
+impl fmt::Display for Counters {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} clock cycles, {} pin writes, {} pin reads, {} delays, {} busy polls",
+			self.clock_cycles, self.pin_writes, self.pin_reads, self.delays, self.busy_polls)
+	}
+}
+
+/// Activity of a single operation; it lasts until the next one starts
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct OperationStats {
+	pub operation: Operation,
+	pub elapsed: Duration,
+	pub counters: Counters,
+}
+
+impl fmt::Display for OperationStats {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}: {:?}, {}", self.operation, self.elapsed, self.counters)
+	}
+}
+
+/// Count pin accesses and clock cycles per operation
+pub struct Statistics<H: Hardware> {
+	hardware: H,
+	start: Instant,
+	clock: bool,
+	counters: Counters,
+	operations: Vec<OperationStats>,
+	// started operation with the time and counters at its start
+	current: Option<(Operation, Instant, Counters)>,
+}
+
+impl<H: Hardware> Statistics<H> {
+	pub fn new(hardware: H) -> Self {
+		let start = hardware.now();
+		Statistics {
+			hardware,
+			start,
+			clock: false,
+			counters: Counters::default(),
+			operations: Vec::new(),
+			current: None,
+		}
+	}
+
+	/// totals since creation (or `clear`)
+	pub fn counters(&self) -> Counters {
+		self.counters
+	}
+
+	pub fn elapsed(&self) -> Duration {
+		self.hardware.now() - self.start
+	}
+
+	/// finished operations, and the current one up to now
+	pub fn operations(&self) -> Vec<OperationStats> {
+		let mut operations = self.operations.clone();
+		operations.extend(self.current_stats(self.hardware.now()));
+		operations
+	}
+
+	pub fn clear(&mut self) {
+		self.start = self.hardware.now();
+		self.counters = Counters::default();
+		self.operations.clear();
+		self.current = None;
+	}
+
+	pub fn into_inner(self) -> H {
+		self.hardware
+	}
+
+	fn current_stats(&self, now: Instant) -> Option<OperationStats> {
+		self.current.map(|(operation, start, counters)| OperationStats {
+			operation,
+			elapsed: now - start,
+			counters: self.counters.since(&counters),
+		})
+	}
+}
+
+impl<H: Hardware> fmt::Display for Statistics<H> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} operations in {:?}: {}", self.operations.len() + self.current.iter().count(), self.elapsed(), self.counters)
+	}
+}
+
+impl<H: Hardware> Hardware for Statistics<H> {
+	fn set_pins(&mut self, pins: OutPins) {
+		self.counters.pin_writes += 1;
+		if pins.clock && !self.clock {
+			self.counters.clock_cycles += 1;
+		}
+		self.clock = pins.clock;
+		self.hardware.set_pins(pins)
+	}
+
+	fn read_pin(&mut self) -> bool {
+		self.counters.pin_reads += 1;
+		self.hardware.read_pin()
+	}
+
+	fn delay(&mut self) {
+		self.counters.delays += 1;
+		self.hardware.delay()
+	}
+
+	fn now(&self) -> Instant {
+		self.hardware.now()
+	}
+
+	fn begin_operation(&mut self, operation: Operation) {
+		let now = self.hardware.now();
+		self.operations.extend(self.current_stats(now));
+		self.current = Some((operation, now, self.counters));
+		self.hardware.begin_operation(operation)
+	}
+
+	fn busy_poll(&mut self) {
+		self.counters.busy_polls += 1;
+		self.hardware.busy_poll()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::Statistics;
+	use crate::serial::{
+		HardwareOperations,
+		Microwire,
+		Operation,
+		Part,
+		Simulator,
+	};
+
+	#[test]
+	fn count_operations() {
+		let geometry = Part::C46.geometry();
+		let mut ee = Microwire::new(Statistics::new(Simulator::new(geometry)), geometry);
+		ee.start_programming().unwrap().write(0x05, 0xbeef).unwrap();
+		assert_eq!(ee.read(0x05).unwrap(), 0xbeef);
+
+		let stats = ee.into_inner();
+		let operations = stats.operations();
+		let kinds: Vec<Operation> = operations.iter().map(|o| o.operation).collect();
+		assert_eq!(kinds, vec![
+			Operation::EraseWriteEnable,
+			Operation::Write(0x05),
+			Operation::EraseWriteDisable,
+			Operation::Read(0x05),
+		]);
+		// the simulated write cycle takes 2ms
+		assert!(operations[1].counters.busy_polls > 0);
+		assert!(operations[1].elapsed >= stats.into_inner().timing().write_cycle);
+		// READY check, start bit, opcode, address, dummy zero bit and data
+		assert_eq!(operations[3].counters.clock_cycles, 1 + 1 + 2 + 6 + 1 + 16);
+	}
+}
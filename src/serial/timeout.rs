@@ -0,0 +1,105 @@
+use std::fmt;
+use std::time::{
+	Duration,
+	Instant,
+};
+
+use failure::Fail;
+
+/// Microwire instruction (for error messages)
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Operation {
+	Read(usize),
+	Write(usize),
+	Erase(usize),
+	EraseAll,
+	WriteAll,
+	EraseWriteEnable,
+	EraseWriteDisable,
+	DetectAddressWidth,
+}
+
+impl fmt::Display for Operation {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Operation::Read(address) => write!(f, "READ @{:02x}", address),
+			Operation::Write(address) => write!(f, "WRITE @{:02x}", address),
+			Operation::Erase(address) => write!(f, "ERASE @{:02x}", address),
+			Operation::EraseAll => write!(f, "ERAL"),
+			Operation::WriteAll => write!(f, "WRAL"),
+			Operation::EraseWriteEnable => write!(f, "EWEN"),
+			Operation::EraseWriteDisable => write!(f, "EWDS"),
+			Operation::DetectAddressWidth => write!(f, "address width detection"),
+		}
+	}
+}
+
+/// Limits for waiting on the EEPROM
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Timeouts {
+	/// waiting for READY after erase/write instructions (and before starting
+	/// a new instruction)
+	pub write_cycle: Duration,
+	/// waiting for the dummy zero bit while detecting the address width
+	pub detect_address_width: Duration,
+}
+
+impl Default for Timeouts {
+	fn default() -> Self {
+		Timeouts {
+			// tWC is at most 10ms in the 93Cx6 datasheets (ERAL/WRAL up to
+			// 15ms on some parts); leave some margin
+			write_cycle: Duration::from_millis(20),
+			// 16 address bits take less than 20 µs at nominal timing, but
+			// each `delay` usually sleeps much longer
+			detect_address_width: Duration::from_millis(100),
+		}
+	}
+}
+
+/// EEPROM didn't respond in time
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Timeout {
+	pub operation: Operation,
+	pub timeout: Duration,
+}
+
+impl fmt::Display for Timeout {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.operation {
+			Operation::DetectAddressWidth => write!(f, "EEPROM {}: no dummy zero bit within {:?}", self.operation, self.timeout),
+			_ => write!(f, "EEPROM {}: not READY within {:?} (missing EEPROM or data line stuck low?)", self.operation, self.timeout),
+		}
+	}
+}
+
+impl Fail for Timeout {
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Deadline {
+	operation: Operation,
+	timeout: Duration,
+	at: Instant,
+}
+
+impl Deadline {
+	pub(super) fn new(operation: Operation, timeout: Duration, now: Instant) -> Self {
+		Deadline {
+			operation,
+			timeout,
+			at: now + timeout,
+		}
+	}
+
+	pub(super) fn check(&self, now: Instant) -> Result<(), Timeout> {
+		if now > self.at {
+			Err(Timeout {
+				operation: self.operation,
+				timeout: self.timeout,
+			})
+		} else {
+			Ok(())
+		}
+	}
+}
@@ -0,0 +1,219 @@
+use std::fmt;
+use std::str;
+use std::time::{
+	Duration,
+	Instant,
+};
+
+use super::{
+	Hardware,
+	OutPins,
+};
+
+/// Minimum timings from the EEPROM datasheet
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct DatasheetTiming {
+	pub clock_high: Duration, // tCKH
+	pub clock_low: Duration, // tCKL
+	pub chip_select_setup: Duration, // tCSS
+	pub data_setup: Duration, // tDIS
+	pub output_delay: Duration, // tPD
+	pub status_valid: Duration, // tSV
+}
+
+impl DatasheetTiming {
+	/// Microchip 93C46B at 4.5V..5.5V
+	pub const MICROCHIP_93C46B: DatasheetTiming = DatasheetTiming {
+		clock_high: Duration::from_nanos(250),
+		clock_low: Duration::from_nanos(250),
+		chip_select_setup: Duration::from_nanos(50),
+		data_setup: Duration::from_nanos(100),
+		output_delay: Duration::from_nanos(400),
+		status_valid: Duration::from_nanos(500),
+	};
+
+	/// Shortest `Hardware::delay` satisfying all timings
+	///
+	/// Pins change only between delays, so CLK high/low and the setup times
+	/// need a single edge; DO (and the READY/BUSY status) is read a full clock
+	/// cycle (two edges) after the rising CLK edge (or CS).
+	pub fn min_edge(&self) -> Duration {
+		let half = |d: Duration| (d + Duration::from_nanos(1)) / 2;
+		self.clock_high
+			.max(self.clock_low)
+			.max(self.chip_select_setup)
+			.max(self.data_setup)
+			.max(half(self.output_delay))
+			.max(half(self.status_valid))
+	}
+}
+
+impl Default for DatasheetTiming {
+	fn default() -> Self {
+		DatasheetTiming::MICROCHIP_93C46B
+	}
+}
+
+/// How to wait for a clock edge
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DelayStrategy {
+	/// `thread::sleep`; usually takes tens of microseconds
+	Sleep,
+	/// spin on the monotonic clock
+	BusySpin,
+	/// read back from the device (flushing posted writes), then spin for
+	/// the remaining time; the bus latency usually covers the full edge
+	Readback,
+}
+
+impl fmt::Display for DelayStrategy {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let name = match *self {
+			DelayStrategy::Sleep => "sleep",
+			DelayStrategy::BusySpin => "spin",
+			DelayStrategy::Readback => "readback",
+		};
+		write!(f, "{}", name)
+	}
+}
+
+impl str::FromStr for DelayStrategy {
+	type Err = ::failure::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"sleep" => Ok(DelayStrategy::Sleep),
+			"spin" | "busy-spin" => Ok(DelayStrategy::BusySpin),
+			"readback" => Ok(DelayStrategy::Readback),
+			_ => bail!("Unknown timing strategy {:?} (expected sleep, spin or readback)", s),
+		}
+	}
+}
+
+/// Wait for at least `duration`, without giving up the CPU
+pub fn busy_wait(duration: Duration) {
+	let start = Instant::now();
+	while start.elapsed() < duration {
+		std::hint::spin_loop();
+	}
+}
+
+/// Delay for a single clock edge
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct EdgeDelay {
+	pub strategy: DelayStrategy,
+	pub edge: Duration,
+}
+
+impl EdgeDelay {
+	pub fn new(strategy: DelayStrategy, timing: &DatasheetTiming) -> Self {
+		EdgeDelay {
+			strategy,
+			edge: timing.min_edge(),
+		}
+	}
+
+	/// `readback` should read from the device the pins were written to
+	pub fn wait<F: FnOnce()>(&self, readback: F) {
+		match self.strategy {
+			DelayStrategy::Sleep => super::hardware::reliable_sleep(self.edge),
+			DelayStrategy::BusySpin => busy_wait(self.edge),
+			DelayStrategy::Readback => {
+				let start = Instant::now();
+				readback();
+				if let Some(remaining) = self.edge.checked_sub(start.elapsed()) {
+					busy_wait(remaining);
+				}
+			},
+		}
+	}
+}
+
+impl Default for EdgeDelay {
+	fn default() -> Self {
+		EdgeDelay::new(DelayStrategy::Sleep, &DatasheetTiming::default())
+	}
+}
+
+/// Achieved bit-bang clock rate
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ClockRate {
+	pub cycles: usize,
+	pub elapsed: Duration,
+}
+
+impl ClockRate {
+	/// in Hz
+	pub fn frequency(&self) -> f64 {
+		self.cycles as f64 / self.elapsed.as_secs_f64()
+	}
+
+	/// average duration of a clock edge (including the pin access)
+	pub fn edge(&self) -> Duration {
+		// (not bounded by u32 like `Duration / u32`)
+		let nanos = self.elapsed.as_nanos() / (2 * self.cycles as u128);
+		Duration::from_nanos(nanos as u64)
+	}
+}
+
+impl fmt::Display for ClockRate {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:.1} kHz ({} cycles in {:?}, {:?} per edge)", self.frequency() / 1000.0, self.cycles, self.elapsed, self.edge())
+	}
+}
+
+/// Toggle CLK (with CS low, so the EEPROM ignores it) and measure the rate
+pub fn measure_clock_rate<H: Hardware + ?Sized>(hardware: &mut H, cycles: usize) -> ClockRate {
+	assert!(cycles > 0);
+	let pins = |clock| OutPins { chip_select: false, clock, data: false };
+	let start = hardware.now();
+	for _ in 0..cycles {
+		hardware.set_pins(pins(true));
+		hardware.delay();
+		hardware.set_pins(pins(false));
+		hardware.delay();
+	}
+	ClockRate {
+		cycles,
+		elapsed: hardware.now() - start,
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use super::{
+		ClockRate,
+		DatasheetTiming,
+		measure_clock_rate,
+	};
+	use crate::serial::{
+		Part,
+		Simulator,
+	};
+
+	#[test]
+	fn min_edge() {
+		assert_eq!(DatasheetTiming::default().min_edge(), Duration::from_nanos(250));
+		let slow = DatasheetTiming {
+			output_delay: Duration::from_nanos(1001),
+			..DatasheetTiming::default()
+		};
+		assert_eq!(slow.min_edge(), Duration::from_nanos(501));
+	}
+
+	#[test]
+	fn measure_simulated_clock() {
+		let mut sim = Simulator::new(Part::C46.geometry());
+		let rate = measure_clock_rate(&mut sim, 1000);
+		assert_eq!(rate.edge(), sim.timing().delay);
+		assert!((rate.frequency() - 2_000_000.0).abs() < 1.0);
+		sim.check().unwrap();
+
+		let rate = ClockRate { cycles: 1 << 31, elapsed: Duration::from_secs(4) };
+		assert_eq!(rate.edge(), Duration::from_nanos(0));
+		let rate = ClockRate { cycles: 1 << 33, elapsed: Duration::from_secs(1 << 20) };
+		assert_eq!(rate.edge(), Duration::from_nanos(61_035));
+	}
+}
@@ -0,0 +1,450 @@
+use std::io;
+use std::time::{
+	Duration,
+	Instant,
+};
+
+use super::{
+	Hardware,
+	Operation,
+	OutPins,
+};
+
+/// State of all EEPROM pins after a change
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Sample {
+	/// time since the trace started
+	pub time: Duration,
+	pub pins: OutPins,
+	/// DO as seen by the last `read_pin` (unknown before the first read)
+	pub data_out: Option<bool>,
+}
+
+/// Records all pin changes of the wrapped `Hardware`
+pub struct Trace<H: Hardware> {
+	hardware: H,
+	start: Instant,
+	recording: bool,
+	current: Sample,
+	samples: Vec<Sample>,
+}
+
+impl<H: Hardware> Trace<H> {
+	pub fn new(hardware: H) -> Self {
+		let start = hardware.now();
+		Trace {
+			hardware,
+			start,
+			recording: true,
+			current: Sample {
+				time: Duration::from_secs(0),
+				// pins are unknown; assume the idle state
+				pins: OutPins { chip_select: false, clock: false, data: false },
+				data_out: None,
+			},
+			samples: Vec::new(),
+		}
+	}
+
+	/// Pause/resume recording (the pins are tracked anyway)
+	pub fn set_recording(&mut self, recording: bool) {
+		self.recording = recording;
+	}
+
+	pub fn samples(&self) -> &[Sample] {
+		&self.samples
+	}
+
+	pub fn clear(&mut self) {
+		self.samples.clear();
+	}
+
+	pub fn into_inner(self) -> H {
+		self.hardware
+	}
+
+	fn record(&mut self, pins: OutPins, data_out: Option<bool>) {
+		if pins == self.current.pins && data_out == self.current.data_out {
+			return;
+		}
+		self.current = Sample {
+			time: self.hardware.now() - self.start,
+			pins,
+			data_out,
+		};
+		if self.recording {
+			self.samples.push(self.current);
+		}
+	}
+
+	/// Write samples as Value Change Dump (e.g. for GTKWave)
+	pub fn write_vcd<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+		write_vcd(&mut w, &self.samples)
+	}
+}
+
+impl<H: Hardware> Hardware for Trace<H> {
+	fn set_pins(&mut self, pins: OutPins) {
+		self.hardware.set_pins(pins);
+		let data_out = self.current.data_out;
+		self.record(pins, data_out);
+	}
+
+	fn read_pin(&mut self) -> bool {
+		let value = self.hardware.read_pin();
+		let pins = self.current.pins;
+		self.record(pins, Some(value));
+		value
+	}
+
+	fn delay(&mut self) {
+		self.hardware.delay();
+	}
+
+	fn now(&self) -> Instant {
+		self.hardware.now()
+	}
+
+	fn begin_operation(&mut self, operation: Operation) {
+		self.hardware.begin_operation(operation)
+	}
+
+	fn busy_poll(&mut self) {
+		self.hardware.busy_poll()
+	}
+}
+
+// identifiers of the VCD variables
+const VCD_CHIP_SELECT: char = 'c';
+const VCD_CLOCK: char = 'k';
+const VCD_DATA_IN: char = 'i';
+const VCD_DATA_OUT: char = 'o';
+
+fn vcd_value(value: Option<bool>) -> char {
+	match value {
+		None => 'x',
+		Some(false) => '0',
+		Some(true) => '1',
+	}
+}
+
+/// Write samples as Value Change Dump (timescale 1ns)
+pub fn write_vcd<W: io::Write + ?Sized>(w: &mut W, samples: &[Sample]) -> io::Result<()> {
+	writeln!(w, "$version {} {} $end", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))?;
+	writeln!(w, "$timescale 1ns $end")?;
+	writeln!(w, "$scope module eeprom $end")?;
+	writeln!(w, "$var wire 1 {} CS $end", VCD_CHIP_SELECT)?;
+	writeln!(w, "$var wire 1 {} CLK $end", VCD_CLOCK)?;
+	writeln!(w, "$var wire 1 {} DI $end", VCD_DATA_IN)?;
+	writeln!(w, "$var wire 1 {} DO $end", VCD_DATA_OUT)?;
+	writeln!(w, "$upscope $end")?;
+	writeln!(w, "$enddefinitions $end")?;
+
+	let mut previous: Option<Sample> = None;
+	for sample in samples {
+		let changed = |f: fn(&Sample) -> Option<bool>| match previous {
+			None => true,
+			Some(ref p) => f(p) != f(sample),
+		};
+		let mut changes = Vec::new();
+		if changed(|s| Some(s.pins.chip_select)) {
+			changes.push((Some(sample.pins.chip_select), VCD_CHIP_SELECT));
+		}
+		if changed(|s| Some(s.pins.clock)) {
+			changes.push((Some(sample.pins.clock), VCD_CLOCK));
+		}
+		if changed(|s| Some(s.pins.data)) {
+			changes.push((Some(sample.pins.data), VCD_DATA_IN));
+		}
+		if changed(|s| s.data_out) {
+			changes.push((sample.data_out, VCD_DATA_OUT));
+		}
+		match previous {
+			None => {
+				writeln!(w, "#{}", sample.time.as_nanos())?;
+				writeln!(w, "$dumpvars")?;
+			},
+			// VCD times must be increasing; multiple changes at the same time
+			// are merged
+			Some(ref p) if !changes.is_empty() && p.time != sample.time => {
+				writeln!(w, "#{}", sample.time.as_nanos())?;
+			},
+			_ => (),
+		}
+		for (value, id) in changes {
+			writeln!(w, "{}{}", vcd_value(value), id)?;
+		}
+		if previous.is_none() {
+			writeln!(w, "$end")?;
+		}
+		previous = Some(*sample);
+	}
+	Ok(())
+}
+
+// signal names used by logic analyzers (and our VCD output)
+fn signal_index(name: &str) -> Option<usize> {
+	match name.trim().to_ascii_lowercase().as_str() {
+		"cs" | "ss" | "chip_select" => Some(0),
+		"clk" | "sk" | "sck" | "clock" => Some(1),
+		"di" | "din" | "mosi" => Some(2),
+		"do" | "dout" | "miso" => Some(3),
+		_ => None,
+	}
+}
+
+fn parse_level(value: &str) -> crate::AResult<Option<bool>> {
+	match value.trim() {
+		"0" | "0.0" => Ok(Some(false)),
+		"1" | "1.0" => Ok(Some(true)),
+		"x" | "X" | "z" | "Z" => Ok(None),
+		v => bail!("invalid signal level {:?}", v),
+	}
+}
+
+// build samples from signal states, dropping entries without changes
+struct SampleBuilder {
+	signals: [Option<bool>; 4],
+	samples: Vec<Sample>,
+}
+
+impl SampleBuilder {
+	fn new() -> Self {
+		SampleBuilder {
+			signals: [None; 4],
+			samples: Vec::new(),
+		}
+	}
+
+	fn push(&mut self, time: Duration) {
+		let sample = Sample {
+			time,
+			pins: OutPins {
+				chip_select: self.signals[0] == Some(true),
+				clock: self.signals[1] == Some(true),
+				data: self.signals[2] == Some(true),
+			},
+			data_out: self.signals[3],
+		};
+		match self.samples.last_mut() {
+			Some(last) if last.time == time => *last = sample,
+			Some(last) if last.pins == sample.pins && last.data_out == sample.data_out => (),
+			_ => self.samples.push(sample),
+		}
+	}
+}
+
+/// Parse logic analyzer CSV export
+///
+/// The header needs a time column (in seconds; the first column) and columns
+/// named CS, CLK (or SK/SCK), DI (or MOSI) and DO (or MISO); DO is optional.
+pub fn parse_csv(text: &str) -> crate::AResult<Vec<Sample>> {
+	let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+	let (_, header) = lines.next().ok_or_else(|| format_err!("empty CSV"))?;
+	let mut columns = [None; 4];
+	for (column, name) in header.split(',').enumerate().skip(1) {
+		if let Some(index) = signal_index(name.trim_matches('"')) {
+			columns[index] = Some(column);
+		}
+	}
+	for (index, name) in ["CS", "CLK", "DI"].iter().enumerate() {
+		ensure!(columns[index].is_some(), "CSV header without {} column: {:?}", name, header);
+	}
+
+	let mut builder = SampleBuilder::new();
+	let mut start = None;
+	for (line_no, line) in lines {
+		with_context!(("CSV line {}", line_no + 1), {
+			let fields: Vec<&str> = line.split(',').collect();
+			let seconds: f64 = fields[0].trim().parse()?;
+			ensure!(seconds.is_finite(), "invalid time {:?}", fields[0]);
+			let start = *start.get_or_insert(seconds);
+			ensure!(seconds >= start, "time {} before start of trace", seconds);
+			for (index, column) in columns.iter().enumerate() {
+				if let Some(column) = *column {
+					let value = fields.get(column).ok_or_else(|| format_err!("missing column {}", column + 1))?;
+					builder.signals[index] = parse_level(value)?;
+				}
+			}
+			builder.push(Duration::from_secs_f64(seconds - start));
+			Ok(())
+		})?;
+	}
+	Ok(builder.samples)
+}
+
+// in picoseconds
+fn parse_timescale(s: &str) -> crate::AResult<u64> {
+	let s = s.trim();
+	let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+	let value: u64 = s[..split].parse()?;
+	ensure!(value > 0, "invalid VCD timescale {:?}", s);
+	let unit: u64 = match s[split..].trim() {
+		"s" => 1_000_000_000_000,
+		"ms" => 1_000_000_000,
+		"us" => 1_000_000,
+		"ns" => 1_000,
+		"ps" => 1,
+		unit => bail!("unsupported VCD timescale unit {:?}", unit),
+	};
+	value.checked_mul(unit).ok_or_else(|| format_err!("VCD timescale {:?} too large", s))
+}
+
+// VCD time `t` in units of `timescale` picoseconds (rounded down to ns)
+fn vcd_time(t: u64, timescale: u64) -> crate::AResult<Duration> {
+	let picoseconds = t.checked_mul(timescale).ok_or_else(|| format_err!("VCD time #{} too large", t))?;
+	Ok(Duration::from_nanos(picoseconds / 1000))
+}
+
+/// Parse Value Change Dump with 1-bit wires named CS, CLK, DI and DO
+pub fn parse_vcd(text: &str) -> crate::AResult<Vec<Sample>> {
+	let mut tokens = text.split_whitespace();
+	let mut timescale = 1000;
+	let mut ids: Vec<(String, usize)> = Vec::new();
+	let mut builder = SampleBuilder::new();
+	let mut time: Option<u64> = None;
+
+	// definitions
+	while let Some(token) = tokens.next() {
+		let mut declaration = Vec::new();
+		for t in &mut tokens {
+			if t == "$end" {
+				break;
+			}
+			declaration.push(t);
+		}
+		match token {
+			"$timescale" => timescale = parse_timescale(&declaration.concat())?,
+			"$var" => {
+				// $var <type> <size> <id> <name> $end
+				ensure!(declaration.len() >= 4, "invalid VCD variable declaration: {:?}", declaration);
+				if let Some(index) = signal_index(declaration[3]) {
+					ensure!(declaration[1] == "1", "VCD variable {} must be 1 bit wide", declaration[3]);
+					ids.push((declaration[2].to_string(), index));
+				}
+			},
+			"$enddefinitions" => break,
+			_ => (),
+		}
+	}
+	for (index, name) in ["CS", "CLK", "DI"].iter().enumerate() {
+		ensure!(ids.iter().any(|&(_, i)| i == index), "VCD without {} variable", name);
+	}
+
+	while let Some(token) = tokens.next() {
+		if let Some(t) = token.strip_prefix('#') {
+			let t: u64 = t.parse().map_err(|e| format_err!("invalid VCD time {:?}: {}", token, e))?;
+			if let Some(previous) = time {
+				ensure!(t >= previous, "VCD time going backwards at {:?}", token);
+				builder.push(vcd_time(previous, timescale)?);
+			}
+			time = Some(t);
+		} else if let "$comment" | "$date" | "$version" = token {
+			// free text up to $end
+			tokens.by_ref().find(|&t| t == "$end");
+		} else if token.starts_with('$') {
+			// $dumpvars, $end, ...
+		} else {
+			let first = token.chars().next().map_or(0, char::len_utf8);
+			let (value, id) = token.split_at(first);
+			if let Some(&(_, index)) = ids.iter().find(|(i, _)| i == id) {
+				builder.signals[index] = parse_level(value)?;
+			}
+		}
+	}
+	if let Some(t) = time {
+		builder.push(vcd_time(t, timescale)?);
+	}
+	Ok(builder.samples)
+}
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use super::{
+		Trace,
+		parse_csv,
+		parse_vcd,
+	};
+	use crate::serial::{
+		HardwareOperations,
+		Microwire,
+		Part,
+		Simulator,
+		decode,
+	};
+
+	#[test]
+	fn trace_read() {
+		let geometry = Part::C46.geometry();
+		let mut sim = Simulator::new(geometry);
+		sim.load(&[0x8001]);
+		let mut ee = Microwire::new(Trace::new(sim), geometry);
+		assert_eq!(ee.read(0).unwrap(), 0x8001);
+
+		let trace = ee.into_inner();
+		let samples = trace.samples();
+		// start bit, opcode and address, dummy zero and data bits: a rising CLK each
+		let rising = samples.windows(2).filter(|s| s[0].pins.chip_select && !s[0].pins.clock && s[1].pins.clock).count();
+		assert!(rising >= 1 + 2 + 6 + 16);
+		assert!(samples.windows(2).all(|s| s[0].time <= s[1].time));
+
+		let mut vcd = Vec::new();
+		trace.write_vcd(&mut vcd).unwrap();
+		let vcd = String::from_utf8(vcd).unwrap();
+		assert!(vcd.contains("$enddefinitions $end"));
+		assert!(vcd.contains("$var wire 1 o DO $end"));
+		assert!(vcd.lines().any(|l| l == "0o"));
+
+		// changes at the same time are merged in VCD
+		let parsed = parse_vcd(&vcd).unwrap();
+		assert_eq!(parsed.last().unwrap().time.as_nanos(), samples.last().unwrap().time.as_nanos());
+		let geometry = Part::C46.geometry();
+		assert_eq!(decode(&parsed, geometry), decode(samples, geometry));
+	}
+
+	#[test]
+	fn parse_logic_analyzer_csv() {
+		let csv = "Time [s],CS,CLK,DI,DO\n\
+			0.000001,0,0,0,1\n\
+			0.000002,1,0,1,1\n\
+			0.000003,1,1,1,1\n\
+			0.000004,1,1,1,1\n\
+			0.000005,0,0,0,1\n";
+		let samples = parse_csv(csv).unwrap();
+		assert_eq!(samples.len(), 4);
+		assert_eq!(samples[0].time, Duration::from_secs(0));
+		assert!(samples[2].pins.chip_select && samples[2].pins.clock);
+		assert_eq!(samples[3].time.as_nanos(), 4000);
+		assert!(parse_csv("Time [s],CLK,DI\n").is_err());
+	}
+
+	#[test]
+	fn vcd_timescales() {
+		let vcd = |timescale: &str, end: &str| format!("$timescale {} $end\n\
+			$var wire 1 c CS $end $var wire 1 k CLK $end $var wire 1 i DI $end\n\
+			$enddefinitions $end\n\
+			#0 0c 0k 0i\n\
+			#1500 1c\n\
+			#{} 0c\n", timescale, end);
+		let samples = parse_vcd(&vcd("1ps", "1000000")).unwrap();
+		assert_eq!(samples[1].time, Duration::from_nanos(1));
+		assert_eq!(samples[2].time, Duration::from_micros(1));
+
+		// beyond u32 units
+		let samples = parse_vcd(&vcd("1 ns", "5000000000")).unwrap();
+		assert_eq!(samples[2].time, Duration::from_secs(5));
+		let samples = parse_vcd(&vcd("10us", "4294967296")).unwrap();
+		assert_eq!(samples[2].time, Duration::from_micros(42_949_672_960));
+
+		assert!(parse_vcd(&vcd("1s", "18446744073709551615")).is_err());
+		assert!(parse_vcd(&vcd("1fs", "2000")).is_err());
+
+		// comments don't change values
+		let text = vcd("1ns", "10000 $comment 1k x $end #20000 ü 0k");
+		let samples = parse_vcd(&text).unwrap();
+		assert!(samples.iter().all(|sample| !sample.pins.clock));
+		assert_eq!(samples.last().unwrap().time, Duration::from_micros(20));
+	}
+}
@@ -0,0 +1,137 @@
+// Defer termination signals while the EEPROM is write-enabled
+//
+// SIGINT, SIGTERM and SIGHUP only set a flag while a `DeferTermination`
+// guard is alive; programming loops poll `check_interrupted` before each
+// unit of work (never between erasing and writing it) and bail out, so
+// destructors can disable writes and release CS.
+// Dropping the last guard restores the previous handlers and re-raises a
+// pending signal.
+
+#[cfg(test)]
+use std::cell::Cell;
+use std::io;
+use std::mem;
+use std::sync::atomic::{
+	AtomicUsize,
+	Ordering,
+};
+use std::sync::Mutex;
+
+use libc::{
+	SA_RESTART,
+	SIGHUP,
+	SIGINT,
+	SIGTERM,
+	c_int,
+	raise,
+	sigaction,
+	sigemptyset,
+	sighandler_t,
+};
+
+const SIGNALS: [c_int; 3] = [SIGINT, SIGTERM, SIGHUP];
+
+// pending signal number (0: none)
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(test)]
+thread_local! {
+	// per thread, so tests running in parallel don't see it
+	static TEST_PENDING: Cell<usize> = const { Cell::new(0) };
+}
+
+struct Installed {
+	depth: usize,
+	previous: Vec<(c_int, sigaction)>,
+}
+
+static INSTALLED: Mutex<Installed> = Mutex::new(Installed {
+	depth: 0,
+	previous: Vec::new(),
+});
+
+extern "C" fn defer_signal(signal: c_int) {
+	PENDING.store(signal as usize, Ordering::SeqCst);
+}
+
+fn signal_name(signal: usize) -> String {
+	match signal as c_int {
+		SIGINT => "SIGINT".into(),
+		SIGTERM => "SIGTERM".into(),
+		SIGHUP => "SIGHUP".into(),
+		_ => format!("signal {}", signal),
+	}
+}
+
+fn restore(previous: &mut Vec<(c_int, sigaction)>) {
+	for (signal, action) in previous.drain(..) {
+		unsafe {
+			sigaction(signal, &action, std::ptr::null_mut());
+		}
+	}
+}
+
+/// Termination signals are deferred while this is alive (may be nested)
+#[must_use]
+pub struct DeferTermination {
+	_private: (),
+}
+
+pub fn defer_termination() -> io::Result<DeferTermination> {
+	let mut installed = INSTALLED.lock().unwrap_or_else(|e| e.into_inner());
+	if 0 == installed.depth {
+		for &signal in &SIGNALS {
+			unsafe {
+				let mut action: sigaction = mem::zeroed();
+				action.sa_sigaction = defer_signal as extern "C" fn(c_int) as sighandler_t;
+				action.sa_flags = SA_RESTART;
+				sigemptyset(&mut action.sa_mask);
+				let mut previous: sigaction = mem::zeroed();
+				if 0 != sigaction(signal, &action, &mut previous) {
+					let e = io::Error::last_os_error();
+					restore(&mut installed.previous);
+					return Err(e);
+				}
+				installed.previous.push((signal, previous));
+			}
+		}
+	}
+	installed.depth += 1;
+	Ok(DeferTermination { _private: () })
+}
+
+impl Drop for DeferTermination {
+	fn drop(&mut self) {
+		let signal = {
+			let mut installed = INSTALLED.lock().unwrap_or_else(|e| e.into_inner());
+			installed.depth -= 1;
+			if 0 != installed.depth {
+				return;
+			}
+			restore(&mut installed.previous);
+			PENDING.swap(0, Ordering::SeqCst)
+		};
+		if 0 != signal {
+			warn!("Terminating after deferred {}", signal_name(signal));
+			unsafe {
+				raise(signal as c_int);
+			}
+		}
+	}
+}
+
+/// Fails if a deferred signal is pending
+pub fn check_interrupted() -> crate::AResult<()> {
+	let signal = PENDING.load(Ordering::SeqCst);
+	#[cfg(test)]
+	let signal = if 0 == signal { TEST_PENDING.with(Cell::get) } else { signal };
+	ensure!(0 == signal, "Interrupted by {}", signal_name(signal));
+	Ok(())
+}
+
+/// Pretend `signal` is pending for `check_interrupted` in the current thread
+/// (0 clears it); nothing gets raised
+#[cfg(test)]
+pub(crate) fn set_test_pending(signal: c_int) {
+	TEST_PENDING.with(|pending| pending.set(signal as usize));
+}